@@ -0,0 +1,29 @@
+//! Parsing worlds from async readers.
+
+use crate::lines::LineParser;
+use crate::{NetLogoWorld, ParseError, ParseOptions};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Parse NetLogo world from an async reader.
+///
+/// Lines are read asynchronously and every complete csv record is
+/// interpreted by the same state machine as [`parse`](crate::parse)
+/// uses, so the input is never buffered as a whole.
+pub async fn parse_async(reader: impl AsyncRead + Unpin) -> Result<NetLogoWorld, ParseError> {
+    parse_async_with(reader, ParseOptions::default()).await
+}
+
+/// Parse NetLogo world from an async reader using `options`.
+pub async fn parse_async_with(
+    reader: impl AsyncRead + Unpin,
+    options: ParseOptions,
+) -> Result<NetLogoWorld, ParseError> {
+    let mut parser = LineParser::new(options);
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    while reader.read_line(&mut line).await? > 0 {
+        parser.push_line(&line)?;
+        line.clear();
+    }
+    parser.finish()
+}
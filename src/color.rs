@@ -0,0 +1,229 @@
+//! NetLogo colors.
+//!
+//! NetLogo encodes colors as numbers in `[0, 140)`. Every ten numbers
+//! make a hue: `x5` is the base color, lower values fade to black and
+//! higher ones to white.
+
+use crate::value::parse_list;
+use crate::Value;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Color of an agent.
+///
+/// NetLogo writes either a color number or an `[r g b]` list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Index(f64),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Color as RGB.
+    pub fn to_rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Index(color) => netlogo_color_to_rgb(color),
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::Index(0.0)
+    }
+}
+
+/// Format as a NetLogo literal.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::Index(color) => write!(f, "{}", color),
+            Color::Rgb(r, g, b) => write!(f, "[{} {} {}]", r, g, b),
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Color::Index(color) => serializer.serialize_f64(color),
+            Color::Rgb(r, g, b) => [r, g, b].serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a color number or an [r g b] list")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Color, E> {
+        Ok(Color::Index(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Color, E> {
+        Ok(Color::Index(v as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Color, E> {
+        Ok(Color::Index(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Color, E> {
+        if let Ok(color) = v.trim().parse() {
+            return Ok(Color::Index(color));
+        }
+        match parse_list(v) {
+            Some(list @ Value::List(_)) => value_to_rgb(&list).map(|(r, g, b)| Color::Rgb(r, g, b)),
+            _ => None,
+        }
+        .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    // Serialized RGB colors
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Color, A::Error> {
+        let mut channel = |i| {
+            seq.next_element::<u8>()?
+                .ok_or_else(|| de::Error::invalid_length(i, &ColorVisitor))
+        };
+        let (r, g, b) = (channel(0)?, channel(1)?, channel(2)?);
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
+        Ok(Color::Rgb(r, g, b))
+    }
+}
+
+/// RGB of the base colors `5`, `15`, ..., `135` as reported by
+/// NetLogo's `extract-rgb`.
+const BASE_COLORS: [(u8, u8, u8); 14] = [
+    (141, 141, 141), // gray
+    (215, 50, 41),   // red
+    (241, 106, 21),  // orange
+    (157, 110, 72),  // brown
+    (237, 237, 49),  // yellow
+    (89, 176, 60),   // green
+    (44, 209, 59),   // lime
+    (29, 159, 120),  // turquoise
+    (84, 196, 196),  // cyan
+    (45, 141, 190),  // sky
+    (52, 93, 169),   // blue
+    (124, 80, 164),  // violet
+    (167, 27, 106),  // magenta
+    (224, 127, 150), // pink
+];
+
+/// Convert NetLogo color number to RGB.
+///
+/// Numbers out of `[0, 140)` are wrapped like NetLogo does.
+pub fn netlogo_color_to_rgb(color: f64) -> (u8, u8, u8) {
+    let color = color.rem_euclid(140.0);
+    let (r, g, b) = BASE_COLORS[(color / 10.0) as usize % BASE_COLORS.len()];
+    let shade = color % 10.0;
+    let mix = |base: u8| -> u8 {
+        let base = f64::from(base);
+        if shade < 5.0 {
+            (base * shade / 5.0) as u8
+        } else {
+            // The lightest shade x.9 is white
+            (base + (255.0 - base) * (shade - 5.0) / 4.9).min(255.0) as u8
+        }
+    };
+    (mix(r), mix(g), mix(b))
+}
+
+/// Convert a color stored in a custom field to RGB.
+///
+/// Handles both color numbers and `[r g b]` lists.
+pub fn value_to_rgb(value: &Value) -> Option<(u8, u8, u8)> {
+    match value {
+        Value::U64(color) => Some(netlogo_color_to_rgb(*color as f64)),
+        Value::I64(color) => Some(netlogo_color_to_rgb(*color as f64)),
+        Value::Float(color) => Some(netlogo_color_to_rgb(*color)),
+        Value::List(rgb) => match rgb.as_slice() {
+            [r, g, b] => Some((channel(r)?, channel(g)?, channel(b)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn channel(value: &Value) -> Option<u8> {
+    match value {
+        Value::U64(v) if *v <= 255 => Some(*v as u8),
+        Value::Float(v) if (0.0..=255.0).contains(v) => Some(*v as u8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_colors() {
+        assert_eq!(netlogo_color_to_rgb(15.0), (215, 50, 41)); // red
+        assert_eq!(netlogo_color_to_rgb(105.0), (52, 93, 169)); // blue
+        assert_eq!(netlogo_color_to_rgb(5.0), (141, 141, 141)); // gray
+    }
+
+    #[test]
+    fn shades() {
+        assert_eq!(netlogo_color_to_rgb(0.0), (0, 0, 0)); // black
+        assert_eq!(netlogo_color_to_rgb(9.9), (255, 255, 255)); // white
+        assert_eq!(netlogo_color_to_rgb(12.5), (107, 25, 20));
+        assert_eq!(netlogo_color_to_rgb(17.5), (235, 154, 150));
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(netlogo_color_to_rgb(155.0), netlogo_color_to_rgb(15.0));
+        assert_eq!(netlogo_color_to_rgb(-35.0), netlogo_color_to_rgb(105.0));
+    }
+
+    fn color(field: &str) -> Color {
+        csv::StringRecord::from(vec![field])
+            .deserialize(None)
+            .expect("deserialize failed")
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(color("15"), Color::Index(15.0));
+        assert_eq!(color("9.9"), Color::Index(9.9));
+        assert_eq!(color("[255 0 128]"), Color::Rgb(255, 0, 128));
+        assert_eq!(color("[255 0 128]").to_rgb(), (255, 0, 128));
+        assert_eq!(color("15").to_rgb(), (215, 50, 41));
+        assert_eq!(Color::Rgb(1, 2, 3).to_string(), "[1 2 3]");
+        assert_eq!(Color::Index(15.0).to_string(), "15");
+    }
+
+    #[test]
+    fn invalid_colors() {
+        let record = csv::StringRecord::from(vec!["[1 2]"]);
+        assert!(record.deserialize::<Color>(None).is_err());
+        let record = csv::StringRecord::from(vec!["red"]);
+        assert!(record.deserialize::<Color>(None).is_err());
+    }
+
+    #[test]
+    fn rgb_lists() {
+        let rgb = Value::List(vec![Value::U64(255), Value::U64(0), Value::U64(128)]);
+        assert_eq!(value_to_rgb(&rgb), Some((255, 0, 128)));
+        assert_eq!(value_to_rgb(&Value::U64(15)), Some((215, 50, 41)));
+        assert_eq!(value_to_rgb(&Value::List(vec![Value::U64(1)])), None);
+        assert_eq!(value_to_rgb(&Value::String("red".into())), None);
+    }
+}
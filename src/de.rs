@@ -0,0 +1,176 @@
+//! Deserialization helpers for NetLogo literals.
+//!
+//! Structs with `#[serde(flatten)]` custom fields get their values
+//! through `deserialize_any`, so csv guesses the type of every
+//! field. Helpers here accept any scalar to work in both modes.
+
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+
+/// Deserialize a NetLogo string literal like `"bug"` into `bug`.
+pub fn string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let literal = deserializer.deserialize_any(TextVisitor)?;
+    Ok(unquote(&literal))
+}
+
+/// Deserialize a NetLogo string literal, an empty one gives `None`.
+pub fn label<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let label = string(deserializer)?;
+    Ok(Some(label).filter(|label| !label.is_empty()))
+}
+
+/// Deserialize a boolean ignoring its case, e.g. `True` or `FALSE`.
+pub fn bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let literal = deserializer.deserialize_any(TextVisitor)?;
+    parse_bool(&literal).ok_or_else(|| de::Error::custom(format!("invalid boolean: {}", literal)))
+}
+
+/// Deserialize an optional boolean, use with `#[serde(default)]`.
+/// An empty field or a serialized `None` gives `None`.
+pub fn option_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    let literal = deserializer.deserialize_any(TextVisitor)?;
+    if literal.is_empty() {
+        return Ok(None);
+    }
+    parse_bool(&literal)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid boolean: {}", literal)))
+}
+
+/// Parse `true` or `false` in any case.
+pub fn parse_bool(token: &str) -> Option<bool> {
+    if token.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if token.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Deserialize an agent reference like `{turtle 3}` (or a bare `3`)
+/// into its `who` number.
+pub fn who<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let literal = deserializer.deserialize_any(TextVisitor)?;
+    let id = literal
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .rsplit(' ')
+        .next()
+        .unwrap_or_default();
+    if id.starts_with('-') {
+        return Err(de::Error::custom(format!("negative who number: {}", id)));
+    }
+    id.parse()
+        .map_err(|_| de::Error::custom(format!("invalid agent reference: {}", literal)))
+}
+
+/// Deserialize a breed reference like `{breed ants}` into `ants`.
+///
+/// Default breeds `{all-turtles}` and `{links}` become `turtles` and
+/// `links`.
+pub fn breed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let literal = deserializer.deserialize_any(TextVisitor)?;
+    let breed = literal.trim_start_matches('{').trim_end_matches('}');
+    let breed = match breed.strip_prefix("breed ") {
+        Some(name) => name,
+        None => breed.trim_start_matches("all-"),
+    };
+    Ok(breed.to_string())
+}
+
+/// Strip the enclosing double quotes of a NetLogo string literal and
+/// unescape its content.
+///
+/// Values without the enclosing quotes are returned as is.
+pub fn unquote(literal: &str) -> String {
+    if literal.len() >= 2 && literal.starts_with('"') && literal.ends_with('"') {
+        unescape(&literal[1..literal.len() - 1])
+    } else {
+        literal.to_string()
+    }
+}
+
+/// Make a NetLogo string literal.
+pub fn quote(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    format!("\"{}\"", escaped)
+}
+
+/// Unescape `\n`, `\t`, `\r`, `\"` and `\\` sequences.
+pub fn unescape(escaped: &str) -> String {
+    let mut result = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c @ '"') | Some(c @ '\\') => result.push(c),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Turn any scalar back into its textual form.
+struct TextVisitor;
+
+impl<'de> Visitor<'de> for TextVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a scalar value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<String, E> {
+        Ok(v.to_string())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<String, E> {
+        Ok(v)
+    }
+
+    // Serialized `None` labels
+    fn visit_none<E: de::Error>(self) -> Result<String, E> {
+        Ok(String::new())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<String, E> {
+        Ok(String::new())
+    }
+}
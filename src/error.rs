@@ -0,0 +1,156 @@
+//! Error type returned by the parser.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while parsing a NetLogo world.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Reading from the underlying source failed.
+    Io(io::Error),
+    /// The data is not a valid csv.
+    Csv(csv::Error),
+    /// A record at `line` could not be interpreted as a part of a
+    /// `section`.
+    Deserialize {
+        section: String,
+        line: u64,
+        source: csv::Error,
+    },
+    /// A required built-in `field` is missing in a `section`.
+    MissingField { section: String, field: String },
+    /// A section appeared where it isn't expected.
+    UnexpectedSection(String),
+    /// "RANDOM STATE" section at `line` is malformed.
+    InvalidRandomState { line: u64, message: String },
+    /// A `section` made of several tables, e.g. "PLOTS", is malformed
+    /// at `line`.
+    InvalidSection {
+        section: String,
+        line: u64,
+        message: String,
+    },
+    /// The world is inconsistent, see [`ParseOptions::validate`](crate::ParseOptions::validate).
+    Invalid(ValidationError),
+    /// The input has no sections, e.g. an empty file.
+    Empty,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "I/O error: {}", err),
+            ParseError::Csv(err) => write!(f, "CSV error: {}", err),
+            ParseError::Deserialize {
+                section,
+                line,
+                source,
+            } => write!(
+                f,
+                "Failed to parse {} section at line {}: {}",
+                section, line, source
+            ),
+            ParseError::MissingField { section, field } => {
+                write!(f, "Missing field '{}' in {} section", field, section)
+            }
+            ParseError::UnexpectedSection(section) => {
+                write!(f, "Unexpected section: {}", section)
+            }
+            ParseError::InvalidRandomState { line, message } => {
+                write!(f, "Invalid random state at line {}: {}", line, message)
+            }
+            ParseError::InvalidSection {
+                section,
+                line,
+                message,
+            } => write!(
+                f,
+                "Invalid {} section at line {}: {}",
+                section, line, message
+            ),
+            ParseError::Invalid(err) => write!(f, "Invalid world: {}", err),
+            ParseError::Empty => write!(f, "No sections found"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            ParseError::Csv(err) => Some(err),
+            ParseError::Deserialize { source, .. } => Some(source),
+            ParseError::Invalid(err) => Some(err),
+            ParseError::MissingField { .. }
+            | ParseError::UnexpectedSection(_)
+            | ParseError::InvalidRandomState { .. }
+            | ParseError::InvalidSection { .. }
+            | ParseError::Empty => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+impl From<ValidationError> for ParseError {
+    fn from(err: ValidationError) -> Self {
+        ParseError::Invalid(err)
+    }
+}
+
+impl From<csv::Error> for ParseError {
+    fn from(err: csv::Error) -> Self {
+        if !err.is_io_error() {
+            return ParseError::Csv(err);
+        }
+        match err.into_kind() {
+            csv::ErrorKind::Io(err) => ParseError::Io(err),
+            // A csv error can't be made of its kind again
+            kind => ParseError::Csv(io::Error::other(format!("{:?}", kind)).into()),
+        }
+    }
+}
+
+/// Inconsistency found by [`NetLogoWorld::validate`](crate::NetLogoWorld::validate).
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// Number of patches doesn't match world dimensions.
+    PatchCount { expected: usize, found: usize },
+    /// Several turtles have the same `who` number.
+    DuplicateWho(usize),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::PatchCount { expected, found } => {
+                write!(f, "Expected {} patches got {}", expected, found)
+            }
+            ValidationError::DuplicateWho(who) => write!(f, "Duplicate turtle {}", who),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Something the parser put up with, see
+/// [`NetLogoWorld::warnings`](crate::NetLogoWorld::warnings).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// A section this crate doesn't know was kept as raw rows.
+    UnknownSection(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnknownSection(name) => write!(f, "Unknown section {} kept as raw rows", name),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Spatial index of patches.
+
+use crate::{Globals, Patch};
+
+/// Patches viewed as a 2D grid.
+///
+/// NetLogo exports patches row by row from the top, i.e. from
+/// `max-pycor` down to `min-pycor`, and from `min-pxcor` to
+/// `max-pxcor` inside of a row, so a patch can be found by its offset.
+///
+/// ```
+/// let data = include_str!("../tests/ants.dat");
+/// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+/// let grid = world.patch_grid();
+/// let patch = grid.get(0, 0).expect("no patch");
+/// assert_eq!((patch.pxcor, patch.pycor), (0, 0));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PatchGrid<'a> {
+    globals: &'a Globals,
+    patches: &'a [Patch],
+}
+
+impl<'a> PatchGrid<'a> {
+    /// Make a grid of `patches` of a world with `globals` bounds.
+    pub fn new(globals: &'a Globals, patches: &'a [Patch]) -> Self {
+        PatchGrid { globals, patches }
+    }
+
+    /// Get a patch at `x`, `y`.
+    ///
+    /// Falls back to a linear search if patches are not in the export
+    /// order.
+    pub fn get(&self, x: i64, y: i64) -> Option<&'a Patch> {
+        let globals = self.globals;
+        if x < globals.min_pxcor
+            || x > globals.max_pxcor
+            || y < globals.min_pycor
+            || y > globals.max_pycor
+        {
+            return None;
+        }
+        let offset = (globals.max_pycor - y) * globals.width() + (x - globals.min_pxcor);
+        match self.patches.get(offset as usize) {
+            Some(patch) if patch.pxcor == x && patch.pycor == y => Some(patch),
+            _ => self
+                .patches
+                .iter()
+                .find(|patch| patch.pxcor == x && patch.pycor == y),
+        }
+    }
+
+    /// Eight patches around `x`, `y` like NetLogo's `neighbors`.
+    ///
+    /// Coordinates are wrapped around the world edges unless the globals
+    /// tell the world doesn't wrap along an axis, then patches beyond
+    /// the edge are left out. A patch is never its own neighbor.
+    ///
+    /// ```
+    /// let data = include_str!("../tests/ants.dat");
+    /// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+    /// assert_eq!(world.patch_grid().neighbors(0, 0).len(), 8);
+    /// ```
+    pub fn neighbors(&self, x: i64, y: i64) -> Vec<&'a Patch> {
+        const OFFSETS: [(i64, i64); 8] = [
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+            (-1, 0),
+            (1, 0),
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+        ];
+        self.adjacent(x, y, &OFFSETS)
+    }
+
+    /// Four patches sharing an edge with `x`, `y` like NetLogo's
+    /// `neighbors4`, see [`neighbors`](Self::neighbors).
+    pub fn neighbors4(&self, x: i64, y: i64) -> Vec<&'a Patch> {
+        const OFFSETS: [(i64, i64); 4] = [(0, 1), (-1, 0), (1, 0), (0, -1)];
+        self.adjacent(x, y, &OFFSETS)
+    }
+
+    fn adjacent(&self, x: i64, y: i64, offsets: &[(i64, i64)]) -> Vec<&'a Patch> {
+        let globals = self.globals;
+        let center = match self.get(x, y) {
+            Some(center) => center,
+            None => return vec![],
+        };
+        let wrap_x = globals.wrapping_allowed_in_x != Some(false);
+        let wrap_y = globals.wrapping_allowed_in_y != Some(false);
+        let mut patches: Vec<&'a Patch> = vec![];
+        for (dx, dy) in offsets {
+            let (mut nx, mut ny) = (x + dx, y + dy);
+            let (wx, wy) = globals.wrap(nx, ny);
+            if wrap_x {
+                nx = wx;
+            }
+            if wrap_y {
+                ny = wy;
+            }
+            let patch = match self.get(nx, ny) {
+                Some(patch) => patch,
+                None => continue,
+            };
+            // Small worlds may wrap onto the same patches
+            let seen =
+                std::ptr::eq(patch, center) || patches.iter().any(|p| std::ptr::eq(*p, patch));
+            if !seen {
+                patches.push(patch);
+            }
+        }
+        patches
+    }
+}
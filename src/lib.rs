@@ -1,7 +1,8 @@
 //! NetLogo `.dat` files parser.
 //!
 //! This library can be used to parse files made by manual exporting
-//! or produced by `export-world` function.
+//! or produced by `export-world` function. Parsed worlds can be
+//! written back with [`write()`].
 //!
 //! ## Implementation details
 //! NetLogo `.dat` files have a header and several sections.  Every
@@ -11,150 +12,822 @@
 //! ### Parsing
 //! The data is parsed as `csv` and interpreted at the same time line
 //! by line. After finding a header the section is read. If a section
-//! is expected to have a header, it's read first.
+//! is expected to have a header, it's read first. Sections may come
+//! in any order and repeat, agents of repeated sections add up.
+//!
+//! [`WorldReader`] yields the parsed data as a stream of [`Event`]s,
+//! [`parse`] collects them into a [`NetLogoWorld`] and [`parse_many`]
+//! into several ones if exports are concatenated.
+//! [`parse_lines`] does the same without `std::io` from an iterator
+//! of lines. [`for_each_record`] only hands raw rows over without
+//! building anything.
+//!
+//! Everything based on `std::io` is behind the default "std" feature,
+//! without it only [`parse_lines`] is left. A `no_std` build isn't
+//! possible yet as `csv` requires `std`.
 //!
 //! ### Parsed data format
-//! Data is typed and uses `custom` hashmap for added properties.
+//! Data is typed and uses `custom` map for added properties. The map
+//! keeps the order of the file columns. Without the "custom-fields"
+//! feature columns of own variables are ignored, their names are still
+//! given by [`NetLogoWorld::fields`].
+//!
+//! Common types are re-exported by [`prelude`].
 //!
-//! TODO: Consider saving "raw" csv data such that a user could
-//! deserialize it to his own data structure.
+//! Agents can also be deserialized into user's own types with
+//! [`parse_turtles_as`], [`parse_patches_as`] and [`parse_links_as`].
 
-use serde::Deserialize;
-use std::error::Error;
-use std::io::Read;
+use csv::StringRecord;
+#[cfg(feature = "std")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{BufReader, Read};
+use std::ops::RangeInclusive;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::str::FromStr;
 
+#[cfg(feature = "async")]
+mod asynchronous;
+pub mod color;
+mod de;
+mod error;
+mod grid;
+mod lines;
+mod options;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod plot;
+pub mod prelude;
+mod random;
+mod reader;
 mod value;
+#[cfg(feature = "std")]
+mod write;
+#[cfg(feature = "async")]
+pub use asynchronous::{parse_async, parse_async_with};
+pub use color::Color;
+pub use error::{ParseError, ValidationError, Warning};
+pub use grid::PatchGrid;
 #[cfg(feature = "custom-fields")]
-use value::Value;
-#[cfg(feature = "custom-fields")]
-use std::collections::HashMap;
+use indexmap::IndexMap;
+pub use lines::{parse_lines, parse_lines_with};
+pub use options::{ParseOptions, SectionMask};
+pub use plot::{Pen, PenMode, Plot, Point};
+pub use random::{RandomState, STATE_WORDS};
+pub use reader::Event;
+#[cfg(feature = "std")]
+pub use reader::{Events, WorldReader};
+pub use value::{Value, ValueRef};
+#[cfg(feature = "std")]
+pub use write::write;
 
 /// Representation of a NetLogo World.
-#[derive(Debug, Deserialize, Default)]
+///
+/// Worlds compare field by field, so a world with a `NaN` float, e.g.
+/// in a custom field, is never equal even to itself. What the parser
+/// learned about the file, e.g. [`NetLogoWorld::sections_seen`], is not
+/// compared.
+#[derive(Debug, Deserialize, Serialize, Default, PartialEq)]
 pub struct NetLogoWorld {
-    pub random_state: Vec<i64>,
+    pub header: Option<Header>,
+    pub random_state: Option<RandomState>,
     pub globals: Globals,
     pub output: Vec<String>,
-    pub turtles: Vec<Turle>,
+    pub turtles: Vec<Turtle>,
     pub patches: Vec<Patch>,
-    pub links: Vec<String>,
-    /// Unimplemented ;(
-    pub plots: (),
+    pub links: Vec<Link>,
+    /// Name of the current plot at the moment of export.
+    pub current_plot: String,
+    pub plots: Vec<Plot>,
+    pub extensions: Vec<Extension>,
+    /// Names and raw rows of sections this crate doesn't know in order
+    /// of appearance, rows of a repeated section are joined.
+    pub unknown_sections: Vec<(String, Vec<Vec<String>>)>,
+    #[serde(skip)]
+    info: ParseInfo,
+}
+
+/// What the parser learned about the file, always equal so it doesn't
+/// affect comparison of worlds.
+#[derive(Debug, Default)]
+struct ParseInfo {
+    sections: Vec<Section>,
+    fields: HashMap<Section, Vec<String>>,
+    warnings: Vec<Warning>,
+    record_counts: HashMap<Section, usize>,
+    /// See [`ParseOptions::keep_float_text`].
+    float_texts: reader::FloatTexts,
+}
+
+impl ParseInfo {
+    /// Add what was learned about `other` file, its rows of agents
+    /// come after `offsets` rows of ours.
+    fn merge(&mut self, other: ParseInfo, offsets: &[(Section, usize)]) {
+        // Globals of `other` replace ours if it has read them
+        if other.fields.contains_key(&Section::Globals) {
+            self.float_texts
+                .retain(|(section, _), _| *section != Section::Globals);
+        }
+        for ((section, row), texts) in other.float_texts {
+            let offset = offsets
+                .iter()
+                .find(|(known, _)| *known == section)
+                .map_or(0, |(_, offset)| *offset);
+            self.float_texts.insert((section, row + offset), texts);
+        }
+        for section in other.sections {
+            if !self.sections.contains(&section) {
+                self.sections.push(section);
+            }
+        }
+        for (section, names) in other.fields {
+            let fields = self.fields.entry(section).or_default();
+            for name in names {
+                if !fields.contains(&name) {
+                    fields.push(name);
+                }
+            }
+        }
+        for (section, count) in other.record_counts {
+            *self.record_counts.entry(section).or_default() += count;
+        }
+        self.warnings.extend(other.warnings);
+    }
+}
+
+impl PartialEq for ParseInfo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl NetLogoWorld {
+    /// Find a turtle by its `who` number.
+    pub fn turtle(&self, who: usize) -> Option<&Turtle> {
+        // Turtles are usually exported sorted by `who`
+        match self.turtles.binary_search_by_key(&who, |turtle| turtle.who) {
+            Ok(i) => Some(&self.turtles[i]),
+            Err(_) => self.turtles.iter().find(|turtle| turtle.who == who),
+        }
+    }
+
+    /// Check that the world is consistent.
+    ///
+    /// Parsing is lenient, so e.g. a truncated export gives fewer
+    /// patches than world dimensions imply. This checks the number of
+    /// patches and that turtle `who` numbers are unique.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let expected = (self.globals.width() * self.globals.height()).max(0) as usize;
+        if self.patches.len() != expected {
+            return Err(ValidationError::PatchCount {
+                expected,
+                found: self.patches.len(),
+            });
+        }
+        let mut who: Vec<usize> = self.turtles.iter().map(|turtle| turtle.who).collect();
+        who.sort_unstable();
+        if let Some(pair) = who.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(ValidationError::DuplicateWho(pair[0]));
+        }
+        Ok(())
+    }
+
+    /// Add agents, plots and other data of `other` world to this one.
+    ///
+    /// Vectors of agents, plots and extensions are concatenated. Globals
+    /// of `other` replace ours if it has read a "GLOBALS" section, so
+    /// its globals win on a conflict. Header, random state, output and
+    /// current plot are taken from `other` only if we don't have them.
+    pub fn merge(&mut self, other: NetLogoWorld) {
+        if !other.global_fields().is_empty() {
+            self.globals = other.globals;
+        }
+        if self.header.is_none() {
+            self.header = other.header;
+        }
+        if self.random_state.is_none() {
+            self.random_state = other.random_state;
+        }
+        if self.output.is_empty() {
+            self.output = other.output;
+        }
+        if self.current_plot.is_empty() {
+            self.current_plot = other.current_plot;
+        }
+        let offsets = [
+            (Section::Turtles, self.turtles.len()),
+            (Section::Patches, self.patches.len()),
+            (Section::Links, self.links.len()),
+        ];
+        self.turtles.extend(other.turtles);
+        self.patches.extend(other.patches);
+        self.links.extend(other.links);
+        self.plots.extend(other.plots);
+        self.extensions.extend(other.extensions);
+        for (name, rows) in other.unknown_sections {
+            self.add_unknown_section(name, rows);
+        }
+        self.info.merge(other.info, &offsets);
+    }
+
+    /// Sections found in the parsed file in order of appearance.
+    ///
+    /// Tells an empty section from a missing one.
+    pub fn sections_seen(&self) -> &[Section] {
+        &self.info.sections
+    }
+
+    /// Number of data rows read per section, excluding headings and
+    /// csv headers.
+    ///
+    /// Tells whether the whole export was loaded, e.g. the count of
+    /// "TURTLES" rows matches the number of turtles.
+    pub fn record_counts(&self) -> &HashMap<Section, usize> {
+        &self.info.record_counts
+    }
+
+    /// Raw rows of an unknown section `name` if it was found.
+    pub fn unknown_section(&self, name: &str) -> Option<&[Vec<String>]> {
+        self.unknown_sections
+            .iter()
+            .find(|(section, _)| section == name)
+            .map(|(_, rows)| rows.as_slice())
+    }
+
+    /// Non-fatal problems found while parsing.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.info.warnings
+    }
+
+    /// Iterate over turtles of a `breed`, the name is case insensitive.
+    pub fn turtles_of_breed<'a>(&'a self, breed: &'a str) -> impl Iterator<Item = &'a Turtle> {
+        self.turtles
+            .iter()
+            .filter(move |turtle| turtle.breed.eq_ignore_ascii_case(breed))
+    }
+
+    /// Distinct breeds of turtles in order of appearance.
+    pub fn breeds(&self) -> Vec<String> {
+        let mut breeds: Vec<String> = vec![];
+        for turtle in &self.turtles {
+            if !breeds.contains(&turtle.breed) {
+                breeds.push(turtle.breed.clone());
+            }
+        }
+        breeds
+    }
+
+    /// Iterate over links of a `breed`, the name is case insensitive.
+    pub fn links_of_breed<'a>(&'a self, breed: &'a str) -> impl Iterator<Item = &'a Link> {
+        self.links
+            .iter()
+            .filter(move |link| link.breed.eq_ignore_ascii_case(breed))
+    }
+
+    /// Column names of a `section` as found in the file.
+    ///
+    /// Empty for missing sections and for sections without a csv
+    /// header.
+    pub fn fields(&self, section: Section) -> &[String] {
+        self.info.fields.get(&section).map_or(&[], Vec::as_slice)
+    }
+
+    /// Names of the global variables, see [`NetLogoWorld::fields`].
+    pub fn global_fields(&self) -> &[String] {
+        self.fields(Section::Globals)
+    }
+
+    /// Names of the turtle variables, see [`NetLogoWorld::fields`].
+    pub fn turtle_fields(&self) -> &[String] {
+        self.fields(Section::Turtles)
+    }
+
+    /// Names of the patch variables, see [`NetLogoWorld::fields`].
+    pub fn patch_fields(&self) -> &[String] {
+        self.fields(Section::Patches)
+    }
+
+    /// Names of the link variables, see [`NetLogoWorld::fields`].
+    pub fn link_fields(&self) -> &[String] {
+        self.fields(Section::Links)
+    }
+
+    /// Iterate over turtles, patches and links in this order.
+    pub fn agents(&self) -> impl Iterator<Item = Agent<'_>> {
+        let turtles = self.turtles.iter().map(Agent::Turtle);
+        let patches = self.patches.iter().map(Agent::Patch);
+        let links = self.links.iter().map(Agent::Link);
+        turtles.chain(patches).chain(links)
+    }
+
+    /// Count turtles of every color number.
+    ///
+    /// Fractional colors are rounded down, turtles with RGB colors are
+    /// not counted.
+    pub fn turtles_by_color(&self) -> HashMap<i64, usize> {
+        let mut counts = HashMap::new();
+        for turtle in &self.turtles {
+            if let Color::Index(color) = turtle.color {
+                *counts.entry(color.floor() as i64).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Counts of agents, ticks and dimensions of the world.
+    ///
+    /// ```
+    /// let data = include_str!("../tests/ants.dat");
+    /// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+    /// println!("{}", world.summary());
+    /// ```
+    pub fn summary(&self) -> WorldSummary {
+        WorldSummary {
+            turtles: self.turtles.len(),
+            patches: self.patches.len(),
+            links: self.links.len(),
+            ticks: self.globals.ticks,
+            width: self.globals.width(),
+            height: self.globals.height(),
+        }
+    }
+
+    /// Average number of turtles per patch.
+    ///
+    /// The number of patches comes from the world bounds, so it's right
+    /// even if "PATCHES" section was skipped. `None` if "GLOBALS"
+    /// section wasn't read or the bounds give no patches.
+    ///
+    /// ```
+    /// let data = include_str!("../tests/ants.dat");
+    /// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+    /// assert_eq!(world.turtle_density(), Some(0.24));
+    /// ```
+    pub fn turtle_density(&self) -> Option<f64> {
+        let patches = self.globals.width() * self.globals.height();
+        if self.global_fields().is_empty() || patches <= 0 {
+            return None;
+        }
+        Some(self.turtles.len() as f64 / patches as f64)
+    }
+
+    /// View patches as a grid for constant time lookups.
+    pub fn patch_grid(&self) -> PatchGrid<'_> {
+        PatchGrid::new(&self.globals, &self.patches)
+    }
+
+    /// Patches in the order NetLogo exports them, i.e. rows from
+    /// `max-pycor` down to `min-pycor` and patches of a row from
+    /// `min-pxcor` to `max-pxcor`, even if `patches` were reordered.
+    pub fn patches_in_export_order(&self) -> Vec<&Patch> {
+        let mut patches: Vec<&Patch> = self.patches.iter().collect();
+        patches.sort_by_key(|patch| (Reverse(patch.pycor), patch.pxcor));
+        patches
+    }
+
+    /// Iterate over patches inside of a rectangle in the export order,
+    /// i.e. from the top row.
+    ///
+    /// ```
+    /// let data = include_str!("../tests/ants.dat");
+    /// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+    /// assert_eq!(world.patches_in(-1..=1, -1..=1).count(), 9);
+    /// ```
+    pub fn patches_in(
+        &self,
+        x_range: RangeInclusive<i64>,
+        y_range: RangeInclusive<i64>,
+    ) -> impl Iterator<Item = &Patch> {
+        let grid = self.patch_grid();
+        let globals = &self.globals;
+        let xs = *x_range.start().max(&globals.min_pxcor)..=*x_range.end().min(&globals.max_pxcor);
+        let ys = *y_range.start().max(&globals.min_pycor)..=*y_range.end().min(&globals.max_pycor);
+        ys.rev()
+            .flat_map(move |y| xs.clone().filter_map(move |x| grid.get(x, y)))
+    }
+
+    /// Find a patch by its coordinates.
+    pub fn patch_at(&self, x: i64, y: i64) -> Option<&Patch> {
+        self.patches
+            .iter()
+            .find(|patch| patch.pxcor == x && patch.pycor == y)
+    }
+}
+
+/// Provenance of an export.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct Header {
+    /// NetLogo version, e.g. `6.1.0`.
+    pub version: String,
+    /// Path of the model file if present.
+    pub model: Option<String>,
+    /// Export timestamp as written by NetLogo.
+    pub exported_at: String,
+}
+
+impl Header {
+    /// Make a header from the lines of "HEADER" section.
+    ///
+    /// The lines are `export-world data (NetLogo 6.1.0)`, an optional
+    /// model path and a timestamp.
+    fn from_lines(mut lines: Vec<String>) -> Header {
+        let exported_at = if lines.len() > 1 {
+            lines.pop().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let model = if lines.len() > 1 { lines.pop() } else { None };
+        let title = lines.pop().unwrap_or_default();
+        let version = title
+            .rsplit('(')
+            .next()
+            .and_then(|version| version.strip_suffix(')'))
+            .map(|version| version.trim_start_matches("NetLogo "))
+            .unwrap_or(&title)
+            .to_string();
+        Header {
+            version,
+            model,
+            exported_at,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Built-in globals of a world.
+///
+/// If a column is repeated, e.g. a user global named `ticks`, the
+/// first one is the typed field and the later ones are kept in custom
+/// fields under the same name.
+#[derive(Debug, Deserialize, Serialize, Default, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Globals {
     pub min_pxcor: i64,
     pub max_pxcor: i64,
     pub min_pycor: i64,
     pub max_pycor: i64,
-    pub ticks: usize,
+    /// Fractional with `tick-advance`. Older versions write it in a
+    /// separate "CLOCK" section.
+    #[serde(default)]
+    pub ticks: f64,
+    /// Whether the world wraps horizontally, `None` if not exported.
+    /// NetLogo worlds wrap by default.
+    #[serde(
+        default,
+        alias = "wrapping-allowed-in-x?",
+        deserialize_with = "de::option_bool"
+    )]
+    pub wrapping_allowed_in_x: Option<bool>,
+    /// Whether the world wraps vertically, `None` if not exported.
+    #[serde(
+        default,
+        alias = "wrapping-allowed-in-y?",
+        deserialize_with = "de::option_bool"
+    )]
+    pub wrapping_allowed_in_y: Option<bool>,
     #[cfg(feature = "custom-fields")]
     #[serde(flatten)]
-    custom: HashMap<String, Value>,
+    custom: IndexMap<String, Value>,
 }
 
 impl Globals {
+    /// Number of patches along x, `world-width` is computed from the
+    /// bounds.
+    pub fn width(&self) -> i64 {
+        self.max_pxcor - self.min_pxcor + 1
+    }
+
+    /// Number of patches along y, `world-height` is computed from the
+    /// bounds.
+    pub fn height(&self) -> i64 {
+        self.max_pycor - self.min_pycor + 1
+    }
+
+    /// Wrap patch coordinates around the world edges like a torus.
+    ///
+    /// Coordinates along an axis with degenerate bounds, i.e. maximum
+    /// less than minimum, are returned unchanged.
+    ///
+    /// ```
+    /// # let world = netlogo_world_parser::parse_lines(include_str!("../tests/ants.dat").lines()).unwrap();
+    /// // The ants world spans from -2 to 2.
+    /// assert_eq!(world.globals.wrap(3, -3), (-2, 2));
+    /// ```
+    pub fn wrap(&self, x: i64, y: i64) -> (i64, i64) {
+        (
+            wrap_coordinate(x, self.min_pxcor, self.width()),
+            wrap_coordinate(y, self.min_pycor, self.height()),
+        )
+    }
+
     /// Get custom field if any.
     ///
     /// Can be used like this:
     /// ```
-    /// u64::try_from(world.globals.get("foo").expect("no foo").to_owned())
+    /// # use std::convert::TryFrom;
+    /// # let world = netlogo_world_parser::parse_lines(include_str!("../tests/ants.dat").lines()).unwrap();
+    /// u64::try_from(world.globals.get("population").expect("no population"))
+    ///     .expect("not a u64");
     /// ```
     #[cfg(feature = "custom-fields")]
     pub fn get(&self, key: &str) -> Option<&Value> {
         self.custom.get(key)
     }
+
+    /// Whether there is a custom field `key`.
+    #[cfg(feature = "custom-fields")]
+    pub fn has(&self, key: &str) -> bool {
+        self.custom.contains_key(key)
+    }
+
+    /// Iterate over custom fields in the order of the file columns.
+    #[cfg(feature = "custom-fields")]
+    pub fn custom_fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.custom.iter().map(|(key, value)| (key.as_str(), value))
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// A turtle.
+///
+/// ```
+/// let data = include_str!("../tests/ants.dat");
+/// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+/// let turtle = &world.turtles[0];
+/// assert_eq!((turtle.xcor, turtle.ycor), (0, 0));
+/// ```
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
-pub struct Turle {
-    who: usize,
-    color: usize,
-    xcor: i64,
-    ycor: i64,
+pub struct Turtle {
+    /// Never negative, numbers of turtles born after others died may
+    /// get large.
+    #[serde(deserialize_with = "de::who")]
+    pub who: usize,
+    pub color: Color,
+    pub xcor: i64,
+    pub ycor: i64,
+    pub heading: f64,
+    #[serde(deserialize_with = "de::string")]
+    pub shape: String,
+    /// `None` for an empty label.
+    #[serde(deserialize_with = "de::label")]
+    pub label: Option<String>,
+    pub label_color: f64,
+    /// Breed name, `turtles` for turtles without a breed.
+    #[serde(deserialize_with = "de::breed")]
+    pub breed: String,
+    pub size: f64,
+    pub pen_size: f64,
+    #[serde(deserialize_with = "de::string")]
+    pub pen_mode: String,
+    #[serde(rename = "hidden?", deserialize_with = "de::bool")]
+    pub hidden: bool,
     #[cfg(feature = "custom-fields")]
     #[serde(flatten)]
-    custom: HashMap<String, Value>,
+    custom: IndexMap<String, Value>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Turtle {
+    /// Get custom field if any.
+    #[cfg(feature = "custom-fields")]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.custom.get(key)
+    }
+
+    /// Whether there is a custom field `key`.
+    #[cfg(feature = "custom-fields")]
+    pub fn has(&self, key: &str) -> bool {
+        self.custom.contains_key(key)
+    }
+
+    /// Iterate over custom fields in the order of the file columns.
+    #[cfg(feature = "custom-fields")]
+    pub fn custom_fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.custom.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Whether the turtle draws when it moves.
+    ///
+    /// `pen-mode` is one of `up`, `down` or `erase`.
+    pub fn is_pen_down(&self) -> bool {
+        self.pen_mode == "down"
+    }
+
+    /// Turtle color as RGB.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        self.color.to_rgb()
+    }
+}
+
+/// Misspelled name of [`Turtle`].
+#[deprecated(note = "use `Turtle` instead")]
+pub type Turle = Turtle;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
 pub struct Patch {
+    pub pxcor: i64,
+    pub pycor: i64,
+    pub pcolor: Color,
+    /// `None` for an empty label.
+    #[serde(deserialize_with = "de::label")]
+    pub plabel: Option<String>,
+    pub plabel_color: f64,
     #[cfg(feature = "custom-fields")]
     #[serde(flatten)]
-    custom: HashMap<String, Value>,
+    custom: IndexMap<String, Value>,
+}
+
+impl Patch {
+    /// Get custom field if any.
+    #[cfg(feature = "custom-fields")]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.custom.get(key)
+    }
+
+    /// Whether there is a custom field `key`.
+    #[cfg(feature = "custom-fields")]
+    pub fn has(&self, key: &str) -> bool {
+        self.custom.contains_key(key)
+    }
+
+    /// Iterate over custom fields in the order of the file columns.
+    #[cfg(feature = "custom-fields")]
+    pub fn custom_fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.custom.iter().map(|(key, value)| (key.as_str(), value))
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
 pub struct Link {
+    /// `who` of the first end.
+    #[serde(deserialize_with = "de::who")]
+    pub end1: usize,
+    /// `who` of the second end.
+    #[serde(deserialize_with = "de::who")]
+    pub end2: usize,
+    pub color: Color,
+    #[serde(deserialize_with = "de::string")]
+    pub label: String,
+    pub label_color: f64,
+    #[serde(rename = "hidden?", deserialize_with = "de::bool")]
+    pub hidden: bool,
+    /// Breed name, `links` for links without a breed.
+    #[serde(deserialize_with = "de::breed")]
+    pub breed: String,
+    /// Read from the `directed?` column, links are undirected in
+    /// exports which don't have it.
+    #[serde(rename = "directed?", default)]
+    pub kind: LinkKind,
+    pub thickness: f64,
+    #[serde(deserialize_with = "de::string")]
+    pub shape: String,
+    #[serde(deserialize_with = "de::string")]
+    pub tie_mode: String,
     #[cfg(feature = "custom-fields")]
     #[serde(flatten)]
-    custom: HashMap<String, Value>,
+    custom: IndexMap<String, Value>,
 }
 
-/// Parse NetLogo world from a str.
-pub fn parse_str(data: &str) -> Result<NetLogoWorld, Box<dyn Error>> {
-    parse(data.as_bytes())
+impl Link {
+    /// Whether the link goes from `end1` to `end2`.
+    pub fn is_directed(&self) -> bool {
+        self.kind.is_directed()
+    }
+
+    /// Get custom field if any.
+    #[cfg(feature = "custom-fields")]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.custom.get(key)
+    }
+
+    /// Whether there is a custom field `key`.
+    #[cfg(feature = "custom-fields")]
+    pub fn has(&self, key: &str) -> bool {
+        self.custom.contains_key(key)
+    }
+
+    /// Iterate over custom fields in the order of the file columns.
+    #[cfg(feature = "custom-fields")]
+    pub fn custom_fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.custom.iter().map(|(key, value)| (key.as_str(), value))
+    }
 }
 
-/// Parse NetLogo world from a reader.
-pub fn parse(reader: impl Read) -> Result<NetLogoWorld, Box<dyn Error>> {
-    let mut headers = None;
-    let mut section = Section::Header;
-    let mut world = NetLogoWorld::default();
+/// Whether a link has a direction, NetLogo writes it as a boolean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkKind {
+    #[default]
+    Undirected,
+    Directed,
+}
 
-    let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+impl LinkKind {
+    pub fn is_directed(self) -> bool {
+        self == LinkKind::Directed
+    }
 
-    for record in rdr.records().map(|record| record.expect("parse error")) {
-        // First check if we are looking on a new section
-        if let Ok(new_section) = record.deserialize::<Section>(None) {
-            section = new_section;
-            headers = None; // reset header
-            continue;
-        }
+    pub fn is_undirected(self) -> bool {
+        self == LinkKind::Undirected
+    }
+}
 
-        // No header? Read one.
-        if section.has_headers() && headers.is_none() {
-            headers = Some(record);
-            continue;
+impl From<bool> for LinkKind {
+    fn from(directed: bool) -> Self {
+        if directed {
+            LinkKind::Directed
+        } else {
+            LinkKind::Undirected
         }
+    }
+}
 
-        match section {
-            Section::RandomState => {
-                world.random_state = record.deserialize(headers.as_ref())?;
-            }
-            Section::Globals => {
-                world.globals = record.deserialize(headers.as_ref())?;
-            }
-            Section::Turtles => {
-                world.turtles.push(record.deserialize(headers.as_ref())?);
-            }
-            Section::Output => {
-                world.output = parse_output(record.deserialize(headers.as_ref())?);
-            }
-            Section::Patches => {
-                world.patches.push(record.deserialize(headers.as_ref())?);
-            }
-            Section::Links => {
-                world.links.push(record.deserialize(headers.as_ref())?);
-            }
-            _ => {
-                // skip the rest for now
-            }
-        }
+impl Serialize for LinkKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(self.is_directed())
     }
-    Ok(world)
 }
 
-// Internal stuff
+impl<'de> Deserialize<'de> for LinkKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        de::bool(deserializer).map(LinkKind::from)
+    }
+}
 
-/// Known file sections
-#[derive(Debug, Deserialize)]
+/// Same as [`parse_str`].
+///
+/// ```
+/// use netlogo_world_parser::NetLogoWorld;
+///
+/// let world: NetLogoWorld = include_str!("../tests/ants.dat")
+///     .parse()
+///     .expect("parse failed");
+/// assert_eq!(world.turtles.len(), 6);
+/// ```
+#[cfg(feature = "std")]
+impl FromStr for NetLogoWorld {
+    type Err = ParseError;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        parse_str(data)
+    }
+}
+
+/// Any agent of a world, see [`NetLogoWorld::agents`].
+#[derive(Debug, Clone, Copy)]
+pub enum Agent<'a> {
+    Turtle(&'a Turtle),
+    Patch(&'a Patch),
+    Link(&'a Link),
+}
+
+/// Overview of a world, see [`NetLogoWorld::summary`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldSummary {
+    pub turtles: usize,
+    pub patches: usize,
+    pub links: usize,
+    pub ticks: f64,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl fmt::Display for WorldSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} world at tick {} with {} turtles, {} patches and {} links",
+            self.width, self.height, self.ticks, self.turtles, self.patches, self.links
+        )
+    }
+}
+
+/// State exported by an extension, e.g. `table`.
+#[derive(Debug, Deserialize, Serialize, Default, PartialEq)]
+pub struct Extension {
+    /// Extension name as written by NetLogo, e.g. `TABLE`.
+    pub name: String,
+    /// Raw rows written by the extension.
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Known file sections.
+///
+/// New sections may be added, so matches need a wildcard arm.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-enum Section {
+pub enum Section {
+    /// Lines before the first section heading.
     Header,
+    #[serde(rename = "RANDOM STATE")]
     RandomState,
     Globals,
     Turtles,
@@ -162,28 +835,437 @@ enum Section {
     Links,
     Output,
     Plots,
-    Extenstions,
+    Extensions,
+    /// Tick counter of older NetLogo versions, also named "TICKS".
+    #[serde(alias = "TICKS")]
+    Clock,
 }
 
 impl Section {
+    /// Section name as it appears in a file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Header => "HEADER",
+            Section::RandomState => "RANDOM STATE",
+            Section::Globals => "GLOBALS",
+            Section::Turtles => "TURTLES",
+            Section::Patches => "PATCHES",
+            Section::Links => "LINKS",
+            Section::Output => "OUTPUT",
+            Section::Plots => "PLOTS",
+            Section::Extensions => "EXTENSIONS",
+            Section::Clock => "CLOCK",
+        }
+    }
+
     /// Whether we expect a header after a section name.
     fn has_headers(&self) -> bool {
-        match self {
-            Section::Header | Section::Output | Section::Plots | Section::Extenstions => false,
-            _ => true,
+        !matches!(
+            self,
+            Section::Header
+                | Section::RandomState
+                | Section::Output
+                | Section::Plots
+                | Section::Extensions
+        )
+    }
+}
+
+/// How often [`parse_with_progress`] reports progress in bytes.
+#[cfg(feature = "std")]
+pub const PROGRESS_STEP: u64 = 64 * 1024;
+
+/// Parse NetLogo world from a str.
+#[cfg(feature = "std")]
+pub fn parse_str(data: &str) -> Result<NetLogoWorld, ParseError> {
+    parse_bytes(data.as_bytes())
+}
+
+/// Parse NetLogo world from bytes, e.g. contents of a file.
+#[cfg(feature = "std")]
+pub fn parse_bytes(data: &[u8]) -> Result<NetLogoWorld, ParseError> {
+    parse(data)
+}
+
+/// Parse NetLogo world from a file at `path`.
+#[cfg(feature = "std")]
+pub fn parse_file(path: impl AsRef<Path>) -> Result<NetLogoWorld, ParseError> {
+    parse(BufReader::new(File::open(path)?))
+}
+
+/// Parse NetLogo world from a gzip compressed reader, e.g. a
+/// `.dat.gz` file.
+#[cfg(feature = "gzip")]
+pub fn parse_gz(reader: impl Read) -> Result<NetLogoWorld, ParseError> {
+    parse(flate2::read::GzDecoder::new(reader))
+}
+
+/// Parse NetLogo world from an `entry_name` file of a zip archive.
+#[cfg(feature = "zip")]
+pub fn parse_zip(
+    reader: impl Read + std::io::Seek,
+    entry_name: &str,
+) -> Result<NetLogoWorld, ParseError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(std::io::Error::from)?;
+    let entry = archive.by_name(entry_name).map_err(std::io::Error::from)?;
+    parse(entry)
+}
+
+/// Parse NetLogo world from a reader.
+///
+/// Input without a single section, e.g. an empty file, fails with
+/// [`ParseError::Empty`] rather than giving an empty world.
+#[cfg(feature = "std")]
+pub fn parse(reader: impl Read) -> Result<NetLogoWorld, ParseError> {
+    parse_with(reader, ParseOptions::default())
+}
+
+/// Parse several worlds concatenated in one stream.
+///
+/// Every world starts with its own header, e.g. when a batch run
+/// appends `export-world` outputs to a single file.
+#[cfg(feature = "std")]
+pub fn parse_many(reader: impl Read) -> Result<Vec<NetLogoWorld>, ParseError> {
+    let mut reader = WorldReader::new(reader);
+    let mut worlds = vec![];
+    let mut world = NetLogoWorld::default();
+    while let Some(event) = reader.next_record()? {
+        if let Some(event) = event {
+            world.push(event);
+        }
+        if std::mem::take(&mut reader.state.new_world) {
+            world.finish(&mut reader.state);
+            world.check_not_empty()?;
+            worlds.push(std::mem::take(&mut world));
         }
     }
+    world.finish(&mut reader.state);
+    world.check_not_empty()?;
+    worlds.push(world);
+    Ok(worlds)
+}
+
+/// Parse NetLogo world from a reader using `options`.
+#[cfg(feature = "std")]
+pub fn parse_with(reader: impl Read, options: ParseOptions) -> Result<NetLogoWorld, ParseError> {
+    parse_with_progress(reader, options, |_| {})
+}
+
+/// Parse NetLogo world from a reader reporting progress.
+///
+/// `on_progress` is called with the number of bytes read every
+/// [`PROGRESS_STEP`] bytes and once at the end.
+///
+/// With `parallel` feature patches are deserialized on a rayon
+/// thread pool after the whole input is read.
+#[cfg(feature = "std")]
+pub fn parse_with_progress(
+    reader: impl Read,
+    options: ParseOptions,
+    mut on_progress: impl FnMut(u64),
+) -> Result<NetLogoWorld, ParseError> {
+    let mut world = NetLogoWorld::default();
+    let validate = options.validate;
+    let mut reader = WorldReader::with_options(reader, options);
+    #[cfg(feature = "parallel")]
+    {
+        reader.state.patch_records = Some(vec![]);
+    }
+    let mut reported = 0;
+    while let Some(event) = reader.next_record()? {
+        if reader.bytes_read() - reported >= PROGRESS_STEP {
+            reported = reader.bytes_read();
+            on_progress(reported);
+        }
+        if let Some(event) = event {
+            world.push(event);
+        }
+    }
+    if reported == 0 || reader.bytes_read() > reported {
+        on_progress(reader.bytes_read());
+    }
+    world.finish(&mut reader.state);
+    world.check_not_empty()?;
+    #[cfg(feature = "parallel")]
+    {
+        let groups = reader.state.patch_records.take().unwrap_or_default();
+        world.patches = parallel::deserialize_patches(&groups)?;
+    }
+    if validate {
+        world.validate()?;
+    }
+    Ok(world)
+}
+
+/// Deserialize "TURTLES" section into user's type.
+///
+/// Only the fields of `T` are read, e.g.:
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Bug {
+///     who: usize,
+///     heading: f64,
+/// }
+///
+/// let data = include_str!("../tests/ants.dat");
+/// let bugs: Vec<Bug> = netlogo_world_parser::parse_turtles_as(data.as_bytes())
+///     .expect("parse failed");
+/// assert_eq!(bugs[1].heading, 266.0);
+/// ```
+///
+/// Note that NetLogo strings are kept quoted, i.e. `"bug"` is read
+/// as `"\"bug\""`.
+#[cfg(feature = "std")]
+pub fn parse_turtles_as<T: DeserializeOwned>(reader: impl Read) -> Result<Vec<T>, ParseError> {
+    parse_section_as(reader, Section::Turtles)
+}
+
+/// Deserialize "PATCHES" section into user's type.
+///
+/// See [`parse_turtles_as`].
+#[cfg(feature = "std")]
+pub fn parse_patches_as<T: DeserializeOwned>(reader: impl Read) -> Result<Vec<T>, ParseError> {
+    parse_section_as(reader, Section::Patches)
+}
+
+/// Deserialize "LINKS" section into user's type.
+///
+/// See [`parse_turtles_as`].
+#[cfg(feature = "std")]
+pub fn parse_links_as<T: DeserializeOwned>(reader: impl Read) -> Result<Vec<T>, ParseError> {
+    parse_section_as(reader, Section::Links)
+}
+
+/// Call `f` with every data row of `reader` and its section without
+/// deserializing anything.
+///
+/// Headings, blank lines, csv headers and rows of unknown sections are
+/// skipped, e.g. to count agents of a huge world:
+///
+/// ```
+/// use netlogo_world_parser::{for_each_record, Section};
+///
+/// let data = include_str!("../tests/ants.dat");
+/// let mut patches = 0;
+/// for_each_record(data.as_bytes(), |section, _| {
+///     if section == Section::Patches {
+///         patches += 1;
+///     }
+/// })
+/// .expect("parse failed");
+/// assert_eq!(patches, 25);
+/// ```
+#[cfg(feature = "std")]
+pub fn for_each_record<F>(reader: impl Read, f: F) -> Result<(), ParseError>
+where
+    F: FnMut(Section, &StringRecord),
+{
+    for_each_record_with(reader, ParseOptions::default(), f)
+}
+
+/// Same as [`for_each_record`] using `options`, only rows of sections
+/// in [`ParseOptions::sections`] are given to `f`.
+#[cfg(feature = "std")]
+pub fn for_each_record_with<F>(
+    reader: impl Read,
+    options: ParseOptions,
+    mut f: F,
+) -> Result<(), ParseError>
+where
+    F: FnMut(Section, &StringRecord),
+{
+    let mut reader = WorldReader::with_options(reader, options);
+    while let Some((section, record)) = reader.next_row()? {
+        f(section, record);
+    }
+    Ok(())
+}
+
+// Internal stuff
+
+impl NetLogoWorld {
+    /// Add a piece of the world read by a [`WorldReader`].
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Header(header) => self.header = Some(header),
+            Event::RandomState(random_state) => self.random_state = Some(random_state),
+            Event::Globals(globals) => self.globals = globals,
+            Event::Ticks(ticks) => self.globals.ticks = ticks,
+            Event::Turtle(turtle) => self.turtles.push(turtle),
+            Event::Patch(patch) => self.patches.push(patch),
+            Event::Link(link) => self.links.push(link),
+            Event::Output(output) => self.output = output,
+            Event::CurrentPlot(current_plot) => self.current_plot = current_plot,
+            Event::Plot(plot) => self.plots.push(plot),
+            Event::Extension(extension) => self.extensions.push(extension),
+            Event::UnknownSection { name, rows } => {
+                self.info
+                    .warnings
+                    .push(Warning::UnknownSection(name.clone()));
+                self.add_unknown_section(name, rows)
+            }
+        }
+    }
+
+    /// Add `rows` of an unknown section `name` after the ones read
+    /// before.
+    fn add_unknown_section(&mut self, name: String, rows: Vec<Vec<String>>) {
+        match self
+            .unknown_sections
+            .iter_mut()
+            .find(|(known, _)| *known == name)
+        {
+            Some((_, known)) => known.extend(rows),
+            None => self.unknown_sections.push((name, rows)),
+        }
+    }
+
+    /// Take what `state` knows about the file after the last event.
+    fn finish(&mut self, state: &mut reader::State) {
+        self.info.sections = std::mem::take(&mut state.sections);
+        self.info.fields = std::mem::take(&mut state.fields);
+        self.info.record_counts = std::mem::take(&mut state.counts);
+        self.info.float_texts = state.float_texts.take().unwrap_or_default();
+    }
+
+    /// Fail if not a single section was found.
+    fn check_not_empty(&self) -> Result<(), ParseError> {
+        if self.info.sections.is_empty() && self.unknown_sections.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        Ok(())
+    }
+}
+
+/// Wrap a coordinate into `size` patches starting at `min`.
+fn wrap_coordinate(v: i64, min: i64, size: i64) -> i64 {
+    if size <= 0 {
+        return v;
+    }
+    min + (v - min).rem_euclid(size)
+}
+
+/// Deserialize every row of `target` section skipping the rest.
+#[cfg(feature = "std")]
+fn parse_section_as<T: DeserializeOwned>(
+    reader: impl Read,
+    target: Section,
+) -> Result<Vec<T>, ParseError> {
+    let options = ParseOptions {
+        sections: SectionMask::none().with(target),
+        ..ParseOptions::default()
+    };
+    let mut reader = WorldReader::with_options(reader, options);
+    let mut rows = vec![];
+    while let Some(row) = reader.next_row_as(target)? {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Deserialize a `record` belonging to a `section`.
+fn deserialize<'de, T: Deserialize<'de>>(
+    section: &Section,
+    record: &'de StringRecord,
+    headers: Option<&'de StringRecord>,
+) -> Result<T, ParseError> {
+    record.deserialize(headers).map_err(|source| {
+        if let Some(field) = missing_field(&source) {
+            return ParseError::MissingField {
+                section: section.name().to_string(),
+                field,
+            };
+        }
+        ParseError::Deserialize {
+            section: section.name().to_string(),
+            line: record.position().map_or(0, |pos| pos.line()),
+            source,
+        }
+    })
+}
+
+/// Get the name of a missing field from serde's error message.
+fn missing_field(err: &csv::Error) -> Option<String> {
+    match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => match err.kind() {
+            csv::DeserializeErrorKind::Message(message) => message
+                .strip_prefix("missing field `")
+                .and_then(|rest| rest.strip_suffix('`'))
+                .map(String::from),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether a record is an upper-cased name on its own line.
+///
+/// Extensions in "EXTENSIONS" section and sections themselves are
+/// introduced this way, while data are NetLogo literals.
+fn is_name(record: &StringRecord) -> bool {
+    record.len() == 1
+        && record[0].starts_with(|c: char| c.is_ascii_uppercase())
+        && record[0]
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || " -_".contains(c))
 }
 
-// TODO: write tests
 /// Parse "OUTPUT" section.
 ///
-/// Remove surrounding double quotes and split the string on escaped
-/// newlines.
+/// The section is a single NetLogo string literal, its lines are
+/// separated either by escaped `\n` or by real line breaks.
 fn parse_output(output: &str) -> Vec<String> {
-    output
-        .trim_matches('"')
-        .split("\\n")
+    de::unquote(output.trim())
+        .lines()
         .map(String::from)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_escaped_newlines() {
+        assert_eq!(parse_output(r#""one\ntwo\n""#), vec!["one", "two"]);
+    }
+
+    #[test]
+    fn output_real_newlines() {
+        assert_eq!(
+            parse_output("\"one\r\ntwo\nthree\""),
+            vec!["one", "two", "three"]
+        );
+        assert_eq!(
+            parse_output("\"one\\ntwo\r\nthree\""),
+            vec!["one", "two", "three"]
+        );
+    }
+
+    #[test]
+    fn output_escapes() {
+        assert_eq!(
+            parse_output(r#""observer: \"Setup complete\"""#),
+            vec![r#"observer: "Setup complete""#]
+        );
+        assert_eq!(parse_output(r#""C:\\models""#), vec![r"C:\models"]);
+    }
+
+    #[test]
+    fn output_enclosing_quotes() {
+        assert_eq!(parse_output(r#""\"quoted\"""#), vec![r#""quoted""#]);
+        assert_eq!(
+            parse_output(r#""\"start\nend\"""#),
+            vec![r#""start"#, r#"end""#]
+        );
+        // Not a literal, kept as is
+        assert_eq!(parse_output(r#"say "hi""#), vec![r#"say "hi""#]);
+    }
+
+    #[test]
+    fn output_whitespace() {
+        assert_eq!(parse_output("  \"  indented\"\r\n"), vec!["  indented"]);
+        assert!(parse_output("").is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Parsing worlds line by line.
+//!
+//! The core doesn't need `std::io`, so it's available without "std"
+//! feature, though the crate still requires `std` as `csv` does.
+
+use crate::reader::State;
+use crate::{NetLogoWorld, ParseError, ParseOptions};
+use csv::{Position, StringRecord};
+use csv_core::ReadRecordResult;
+
+/// Parse NetLogo world from its lines.
+///
+/// ```
+/// let data = include_str!("../tests/ants.dat");
+/// let world = netlogo_world_parser::parse_lines(data.lines()).expect("parse failed");
+/// assert_eq!(world.turtles.len(), 6);
+/// ```
+pub fn parse_lines<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+) -> Result<NetLogoWorld, ParseError> {
+    parse_lines_with(lines, ParseOptions::default())
+}
+
+/// Parse NetLogo world from its lines using `options`.
+pub fn parse_lines_with<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    options: ParseOptions,
+) -> Result<NetLogoWorld, ParseError> {
+    let mut parser = LineParser::new(options);
+    for line in lines {
+        parser.push_line(line)?;
+    }
+    parser.finish()
+}
+
+/// Groups lines into csv records and interprets them.
+pub(crate) struct LineParser {
+    validate: bool,
+    state: State,
+    world: NetLogoWorld,
+    /// Splits records into fields, reused for every record.
+    csv: csv_core::Reader,
+    /// Lines of the current record, quoted fields may have line breaks.
+    lines: String,
+    /// Whether the lines end inside of a quoted field.
+    quoted: bool,
+    line: u64,
+    /// Line number where the current record starts.
+    first: u64,
+    /// Unescaped fields of the current record.
+    output: Vec<u8>,
+    /// Ends of the fields in `output`.
+    ends: Vec<usize>,
+}
+
+impl LineParser {
+    pub(crate) fn new(options: ParseOptions) -> Self {
+        LineParser {
+            validate: options.validate,
+            csv: csv_core::ReaderBuilder::new()
+                .delimiter(options.delimiter)
+                .comment(options.comment)
+                .build(),
+            state: State::new(options),
+            world: NetLogoWorld::default(),
+            lines: String::new(),
+            quoted: false,
+            line: 0,
+            first: 1,
+            output: vec![0; 1024],
+            ends: vec![0; 64],
+        }
+    }
+
+    /// Add a `line` with or without its line break.
+    pub(crate) fn push_line(&mut self, line: &str) -> Result<(), ParseError> {
+        self.line += 1;
+        // Windows exports may start with a UTF-8 BOM
+        let line = if self.line == 1 {
+            line.trim_start_matches('\u{feff}')
+        } else {
+            line
+        };
+        self.lines.push_str(line);
+        if !line.ends_with('\n') {
+            self.lines.push('\n');
+        }
+        if line.matches('"').count() % 2 == 1 {
+            self.quoted = !self.quoted;
+        }
+        if !self.quoted {
+            self.read_record()?;
+        }
+        Ok(())
+    }
+
+    /// Interpret the remaining lines and return the world.
+    pub(crate) fn finish(mut self) -> Result<NetLogoWorld, ParseError> {
+        self.read_record()?;
+        while let Some(event) = self.state.flush() {
+            self.world.push(event);
+        }
+        self.world.finish(&mut self.state);
+        self.world.check_not_empty()?;
+        if self.validate {
+            self.world.validate()?;
+        }
+        Ok(self.world)
+    }
+
+    fn read_record(&mut self) -> Result<(), ParseError> {
+        if let Some(record) = self.split_record() {
+            if let Some(event) = self.state.feed(record)? {
+                self.world.push(event);
+            }
+        }
+        self.lines.clear();
+        self.quoted = false;
+        self.first = self.line + 1;
+        Ok(())
+    }
+
+    /// Split the current lines into fields, `None` if they are blank
+    /// or a comment.
+    fn split_record(&mut self) -> Option<StringRecord> {
+        self.csv.reset();
+        let mut input = self.lines.as_bytes();
+        let (mut out, mut end) = (0, 0);
+        loop {
+            let (result, nin, nout, nend) =
+                self.csv
+                    .read_record(input, &mut self.output[out..], &mut self.ends[end..]);
+            input = &input[nin..];
+            out += nout;
+            end += nend;
+            match result {
+                // The next call with no input ends the record
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => {
+                    let len = self.output.len();
+                    self.output.resize(len * 2, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    let len = self.ends.len();
+                    self.ends.resize(len * 2, 0);
+                }
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => return None,
+            }
+        }
+
+        let mut record = StringRecord::new();
+        let mut start = 0;
+        for &field_end in &self.ends[..end] {
+            // Fields of a `str` split at ASCII bytes are valid UTF-8
+            let field = std::str::from_utf8(&self.output[start..field_end]).unwrap_or_default();
+            record.push_field(field);
+            start = field_end;
+        }
+        let mut position = Position::new();
+        position.set_line(self.first);
+        record.set_position(Some(position));
+        Some(record)
+    }
+}
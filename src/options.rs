@@ -0,0 +1,100 @@
+//! Parsing options.
+
+use crate::Section;
+
+/// Options of [`parse_with`](crate::parse_with) and
+/// [`WorldReader::with_options`](crate::WorldReader::with_options).
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Field delimiter, some locales export files with `;`.
+    pub delimiter: u8,
+    /// Skip lines starting with this byte, e.g. `Some(b';')` for hand
+    /// annotated files. Off by default as real data may start with it.
+    pub comment: Option<u8>,
+    /// Sections to parse, rows of the other ones are skipped.
+    pub sections: SectionMask,
+    /// Fail with [`ParseError::Invalid`](crate::ParseError::Invalid) if
+    /// the parsed world doesn't pass
+    /// [`NetLogoWorld::validate`](crate::NetLogoWorld::validate), e.g.
+    /// has duplicate `who` numbers.
+    pub validate: bool,
+    /// Lowercase column names before matching them, so custom fields
+    /// can be looked up regardless of the declared casing. Raw names
+    /// are still given by [`NetLogoWorld::fields`](crate::NetLogoWorld::fields).
+    pub normalize_keys: bool,
+    /// Fail with [`ParseError::UnexpectedSection`](crate::ParseError::UnexpectedSection)
+    /// on sections this crate doesn't know instead of keeping their raw
+    /// rows, e.g. to catch exports of newer NetLogo versions.
+    pub strict_sections: bool,
+    /// Remember the original text of floats which format differently,
+    /// e.g. `1.5E-7`, so [`write`](crate::write()) gives them back byte
+    /// for byte. Texts are kept per agent and column of globals and
+    /// agents, a changed value is written as usual. Floats of plots
+    /// and inside of lists aren't remembered.
+    pub keep_float_text: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            delimiter: b',',
+            comment: None,
+            sections: SectionMask::all(),
+            validate: false,
+            normalize_keys: false,
+            strict_sections: false,
+            keep_float_text: false,
+        }
+    }
+}
+
+/// Set of sections.
+///
+/// ```
+/// use netlogo_world_parser::{Section, SectionMask};
+///
+/// let mask = SectionMask::none()
+///     .with(Section::Globals)
+///     .with(Section::Turtles);
+/// assert!(mask.contains(Section::Turtles));
+/// assert!(!mask.contains(Section::Patches));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionMask(u32);
+
+impl SectionMask {
+    /// Every section.
+    pub fn all() -> Self {
+        SectionMask(u32::MAX)
+    }
+
+    /// No sections.
+    pub fn none() -> Self {
+        SectionMask(0)
+    }
+
+    /// Add a `section` to the set.
+    pub fn with(self, section: Section) -> Self {
+        SectionMask(self.0 | Self::bit(section))
+    }
+
+    /// Remove a `section` from the set.
+    pub fn without(self, section: Section) -> Self {
+        SectionMask(self.0 & !Self::bit(section))
+    }
+
+    /// Whether the set has a `section`.
+    pub fn contains(&self, section: Section) -> bool {
+        self.0 & Self::bit(section) != 0
+    }
+
+    fn bit(section: Section) -> u32 {
+        1 << section as u32
+    }
+}
+
+impl Default for SectionMask {
+    fn default() -> Self {
+        Self::all()
+    }
+}
@@ -0,0 +1,23 @@
+//! Deserializing patches on a rayon thread pool.
+
+use crate::{deserialize, ParseError, Patch, Section};
+use csv::StringRecord;
+use rayon::prelude::*;
+
+/// Deserialize raw patch records grouped by their headers in parallel
+/// keeping their order.
+pub(crate) fn deserialize_patches(
+    groups: &[(StringRecord, Vec<StringRecord>)],
+) -> Result<Vec<Patch>, ParseError> {
+    groups
+        .par_iter()
+        .flat_map(|(headers, records)| records.par_iter().map(move |record| (headers, record)))
+        .map(|(headers, record)| {
+            #[allow(unused_mut)]
+            let mut patch: Patch = deserialize(&Section::Patches, record, Some(headers))?;
+            #[cfg(feature = "custom-fields")]
+            crate::reader::unshadow(&mut patch.custom);
+            Ok(patch)
+        })
+        .collect()
+}
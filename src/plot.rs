@@ -0,0 +1,266 @@
+//! "PLOTS" section parser.
+//!
+//! Unlike other sections, "PLOTS" consists of several csv tables per
+//! plot:
+//! ```text
+//! "current plot name"
+//! """plot name"""
+//! "x min","x max",...,"number of pens"
+//! <plot settings>
+//! "pen name","pen down?",...
+//! <a row per pen>
+//! """pen 1""",,,,"""pen 2""",,,
+//! "x","y","color","pen down?","x","y","color","pen down?"
+//! <points of all pens, four columns per pen>
+//! ```
+
+use crate::{de, deserialize, ParseError, Section};
+use csv::StringRecord;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A plot with its pens.
+#[derive(Debug, Deserialize, Serialize, Default, PartialEq)]
+pub struct Plot {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "x min")]
+    pub x_min: f64,
+    #[serde(rename = "x max")]
+    pub x_max: f64,
+    #[serde(rename = "y min")]
+    pub y_min: f64,
+    #[serde(rename = "y max")]
+    pub y_max: f64,
+    #[serde(rename = "autoplot?", deserialize_with = "de::bool")]
+    pub autoplot: bool,
+    #[serde(rename = "current pen", deserialize_with = "de::string")]
+    pub current_pen: String,
+    #[serde(rename = "legend open?", deserialize_with = "de::bool")]
+    pub legend_open: bool,
+    #[serde(default)]
+    pub pens: Vec<Pen>,
+}
+
+impl Plot {
+    /// Get pen by name.
+    pub fn pen(&self, name: &str) -> Option<&Pen> {
+        self.pens.iter().find(|pen| pen.name == name)
+    }
+}
+
+/// Plot pen together with the points it has drawn.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Pen {
+    #[serde(rename = "pen name", deserialize_with = "de::string")]
+    pub name: String,
+    #[serde(rename = "pen down?", deserialize_with = "de::bool")]
+    pub pen_down: bool,
+    /// Missing in old exports, then pens draw lines.
+    #[serde(rename = "mode", default)]
+    pub pen_mode: PenMode,
+    pub interval: f64,
+    /// Color number or a packed RGB value, points have their colors
+    /// too.
+    #[serde(rename = "color", default)]
+    pub pen_color: f64,
+    /// Next x coordinate of the pen.
+    pub x: f64,
+    #[serde(default)]
+    pub points: Vec<Point>,
+}
+
+/// How a pen draws its points, NetLogo writes it as a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PenMode {
+    #[default]
+    Line,
+    Bar,
+    Point,
+}
+
+impl PenMode {
+    fn number(self) -> u8 {
+        match self {
+            PenMode::Line => 0,
+            PenMode::Bar => 1,
+            PenMode::Point => 2,
+        }
+    }
+}
+
+/// Format as a NetLogo number.
+impl fmt::Display for PenMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+impl Serialize for PenMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.number())
+    }
+}
+
+impl<'de> Deserialize<'de> for PenMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(PenMode::Line),
+            1 => Ok(PenMode::Bar),
+            2 => Ok(PenMode::Point),
+            mode => Err(serde::de::Error::custom(format!(
+                "invalid pen mode: {}",
+                mode
+            ))),
+        }
+    }
+}
+
+/// A point drawn by a pen.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub color: f64,
+    #[serde(rename = "pen down?", deserialize_with = "de::bool")]
+    pub pen_down: bool,
+}
+
+/// Where we are inside of the "PLOTS" section.
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    CurrentPlot,
+    Name,
+    SettingsHeaders,
+    Settings,
+    PensHeaders,
+    Pens {
+        left: usize,
+    },
+    PointsPens,
+    PointsHeaders,
+    Points,
+}
+
+/// Reads "PLOTS" section record by record.
+#[derive(Debug, Default)]
+pub(crate) struct PlotsReader {
+    state: State,
+    headers: Option<StringRecord>,
+    plot: Option<Plot>,
+    /// Number of pens of the current plot.
+    pens: usize,
+    /// Name of the plot which was current at the moment of export.
+    pub current: Option<String>,
+}
+
+impl PlotsReader {
+    /// Read a next record of the section.
+    ///
+    /// Returns the previous plot when the record starts a new one.
+    pub fn read(&mut self, record: &StringRecord) -> Result<Option<Plot>, ParseError> {
+        let section = &Section::Plots;
+        let mut finished = None;
+        match self.state {
+            State::CurrentPlot => {
+                self.current = record.get(0).map(String::from);
+                self.state = State::Name;
+            }
+            State::Points if record.len() > 1 => {
+                let plot = self
+                    .plot
+                    .as_mut()
+                    .ok_or_else(|| invalid(record, "no plot"))?;
+                let headers = self.headers.as_ref();
+                for (i, pen) in plot.pens.iter_mut().enumerate() {
+                    let point = chunk(record, i);
+                    if point.iter().all(str::is_empty) {
+                        continue;
+                    }
+                    let point_headers = headers.map(|headers| chunk(headers, i));
+                    pen.points
+                        .push(deserialize(section, &point, point_headers.as_ref())?);
+                }
+            }
+            State::Name | State::Points => {
+                if record.iter().skip(1).any(|field| !field.is_empty()) {
+                    return Err(invalid(record, "expected a plot name"));
+                }
+                finished = self.plot.take();
+                self.plot = Some(Plot {
+                    name: de::unquote(record.get(0).unwrap_or_default()),
+                    ..Plot::default()
+                });
+                self.state = State::SettingsHeaders;
+            }
+            State::SettingsHeaders => {
+                self.headers = Some(record.clone());
+                self.state = State::Settings;
+            }
+            State::Settings => {
+                let headers = self.headers.as_ref();
+                let plot: Plot = deserialize(section, record, headers)?;
+                let pens = headers
+                    .and_then(|headers| headers.iter().position(|h| h == "number of pens"))
+                    .and_then(|i| record.get(i))
+                    .and_then(|pens| pens.parse().ok())
+                    .unwrap_or(0);
+                let name = self.plot.take().map(|plot| plot.name).unwrap_or_default();
+                self.plot = Some(Plot { name, ..plot });
+                self.pens = pens;
+                self.state = State::PensHeaders;
+            }
+            State::PensHeaders => {
+                self.headers = Some(record.clone());
+                self.state = match self.pens {
+                    0 => State::Points,
+                    left => State::Pens { left },
+                };
+            }
+            State::Pens { left } => {
+                let pen = deserialize(section, record, self.headers.as_ref())?;
+                let plot = self
+                    .plot
+                    .as_mut()
+                    .ok_or_else(|| invalid(record, "no plot"))?;
+                plot.pens.push(pen);
+                self.state = match left {
+                    1 => State::PointsPens,
+                    left => State::Pens { left: left - 1 },
+                };
+            }
+            State::PointsPens => {
+                self.state = State::PointsHeaders;
+            }
+            State::PointsHeaders => {
+                self.headers = Some(record.clone());
+                self.state = State::Points;
+            }
+        }
+        Ok(finished)
+    }
+
+    /// Finish the section returning the last plot if any.
+    pub fn finish(&mut self) -> Option<Plot> {
+        self.state = State::default();
+        self.headers = None;
+        self.plot.take()
+    }
+}
+
+/// Make an error about a malformed `record`.
+fn invalid(record: &StringRecord, message: &str) -> ParseError {
+    ParseError::InvalidSection {
+        section: Section::Plots.name().to_string(),
+        line: record.position().map_or(0, |pos| pos.line()),
+        message: message.to_string(),
+    }
+}
+
+/// Get four columns belonging to the `n`th pen.
+fn chunk(record: &StringRecord, n: usize) -> StringRecord {
+    let mut chunk: StringRecord = record.iter().skip(n * 4).take(4).collect();
+    chunk.set_position(record.position().cloned());
+    chunk
+}
@@ -0,0 +1,19 @@
+//! Common imports.
+//!
+//! ```
+//! use netlogo_world_parser::prelude::*;
+//!
+//! let data = include_str!("../tests/ants.dat");
+//! let world: NetLogoWorld = parse_lines(data.lines()).expect("parse failed");
+//! let turtle: &Turtle = &world.turtles[0];
+//! assert_eq!(turtle.color, Color::Index(15.0));
+//! assert_eq!(u64::try_from(Value::U64(6)), Ok(6));
+//! ```
+
+#[cfg(feature = "std")]
+pub use crate::{parse, parse_bytes, parse_file, parse_str, parse_with, write, WorldReader};
+pub use crate::{
+    parse_lines, Agent, Color, Event, Globals, Link, LinkKind, NetLogoWorld, ParseError,
+    ParseOptions, Patch, Section, SectionMask, Turtle, Value,
+};
+pub use std::convert::{TryFrom, TryInto};
@@ -0,0 +1,532 @@
+//! Streaming reader of NetLogo worlds.
+
+use crate::plot::{Plot, PlotsReader};
+use crate::random::RandomState;
+#[cfg(feature = "custom-fields")]
+use crate::Value;
+use crate::{
+    deserialize, is_name, parse_output, Extension, Globals, Header, Link, ParseError, ParseOptions,
+    Patch, Section, SectionMask, Turtle,
+};
+use csv::StringRecord;
+#[cfg(feature = "custom-fields")]
+use indexmap::IndexMap;
+#[cfg(feature = "std")]
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+/// A piece of a world produced by [`WorldReader`].
+#[derive(Debug)]
+pub enum Event {
+    Header(Header),
+    RandomState(RandomState),
+    Globals(Globals),
+    /// Ticks from a legacy "CLOCK" section.
+    Ticks(f64),
+    Turtle(Turtle),
+    Patch(Patch),
+    Link(Link),
+    Output(Vec<String>),
+    /// Name of the current plot at the moment of export.
+    CurrentPlot(String),
+    Plot(Plot),
+    Extension(Extension),
+    /// Section this crate doesn't know with its raw rows.
+    UnknownSection {
+        name: String,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+/// Reads a world event by event without keeping it in memory.
+///
+/// ```
+/// use netlogo_world_parser::{Event, WorldReader};
+///
+/// let data = include_str!("../tests/ants.dat");
+/// let mut reader = WorldReader::new(data.as_bytes());
+/// for event in reader.events() {
+///     if let Event::Turtle(turtle) = event.expect("parse failed") {
+///         println!("{:?}", turtle);
+///     }
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub struct WorldReader<R> {
+    rdr: csv::Reader<R>,
+    pub(crate) state: State,
+}
+
+/// Interpretation of records regardless of where they are read from.
+pub(crate) struct State {
+    pub(crate) record: StringRecord,
+    pub(crate) section: Section,
+    pub(crate) headers: Option<StringRecord>,
+    plots: PlotsReader,
+    extension: Option<Extension>,
+    /// Name and rows of an unknown section being read.
+    unknown: Option<(String, Vec<Vec<String>>)>,
+    /// Lines of "HEADER" section.
+    header: Vec<String>,
+    pub(crate) sections: Vec<Section>,
+    /// Column names of sections with a csv header, later headers of
+    /// the same section add their new columns.
+    pub(crate) fields: HashMap<Section, Vec<String>>,
+    /// Original text of floats if asked to keep it.
+    pub(crate) float_texts: Option<FloatTexts>,
+    /// Number of data rows read per section.
+    pub(crate) counts: HashMap<Section, usize>,
+    /// Sections to parse.
+    mask: SectionMask,
+    /// Lowercase csv headers.
+    normalize_keys: bool,
+    /// Fail on unknown sections.
+    strict_sections: bool,
+    /// Set when a next concatenated world starts.
+    pub(crate) new_world: bool,
+    /// Raw patch rows grouped by their headers to deserialize later
+    /// instead of emitting events.
+    #[cfg(feature = "parallel")]
+    pub(crate) patch_records: Option<Vec<(StringRecord, Vec<StringRecord>)>>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> WorldReader<R> {
+    /// Create a reader of a world from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Create a reader of a world from `reader` using `options`.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        WorldReader {
+            rdr: csv_reader(reader, &options),
+            state: State::new(options),
+        }
+    }
+
+    /// Iterate over the remaining events.
+    pub fn events(&mut self) -> Events<'_, R> {
+        Events { reader: self }
+    }
+
+    /// Section headings found so far in order of appearance.
+    pub fn sections_seen(&self) -> &[Section] {
+        &self.state.sections
+    }
+
+    /// Number of bytes consumed from the underlying reader so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.rdr.position().byte()
+    }
+
+    /// Column names of a `section` if it was read already.
+    pub fn fields(&self, section: Section) -> Option<&[String]> {
+        self.state.fields.get(&section).map(Vec::as_slice)
+    }
+
+    /// Read a next event, `None` means the end of input.
+    pub fn next_event(&mut self) -> Result<Option<Event>, ParseError> {
+        while let Some(event) = self.next_record()? {
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read a next record, `None` means the end of input while
+    /// `Some(None)` is a record which didn't make an event.
+    pub(crate) fn next_record(&mut self) -> Result<Option<Option<Event>>, ParseError> {
+        if !self.rdr.read_record(&mut self.state.record)? {
+            return Ok(self.state.flush().map(Some));
+        }
+        self.state.read().map(Some)
+    }
+
+    /// Read a next data row of a known section, skipping headings, csv
+    /// headers and rows of unknown or masked sections.
+    ///
+    /// Nothing is deserialized, e.g. to count agents of a huge file:
+    ///
+    /// ```
+    /// use netlogo_world_parser::{Section, WorldReader};
+    ///
+    /// let data = include_str!("../tests/ants.dat");
+    /// let mut reader = WorldReader::new(data.as_bytes());
+    /// let mut turtles = 0;
+    /// while let Some((section, _)) = reader.next_row().expect("parse failed") {
+    ///     turtles += (section == Section::Turtles) as usize;
+    /// }
+    /// assert_eq!(turtles, 6);
+    /// ```
+    pub fn next_row(&mut self) -> Result<Option<(Section, &StringRecord)>, ParseError> {
+        loop {
+            if !self.rdr.read_record(&mut self.state.record)? {
+                return Ok(None);
+            }
+            if let Step::Row = self.state.classify()? {
+                return Ok(Some((self.state.section, &self.state.record)));
+            }
+        }
+    }
+
+    /// Deserialize a next row of a `section` into user's type skipping
+    /// the other rows, see [`parse_turtles_as`](crate::parse_turtles_as).
+    pub fn next_row_as<T: DeserializeOwned>(
+        &mut self,
+        section: Section,
+    ) -> Result<Option<T>, ParseError> {
+        while let Some((next, _)) = self.next_row()? {
+            if next == section {
+                let state = &self.state;
+                return deserialize(&section, &state.record, state.headers.as_ref()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl State {
+    pub(crate) fn new(options: ParseOptions) -> Self {
+        State {
+            record: StringRecord::new(),
+            section: Section::Header,
+            headers: None,
+            plots: PlotsReader::default(),
+            extension: None,
+            unknown: None,
+            header: vec![],
+            sections: vec![],
+            fields: HashMap::new(),
+            float_texts: if options.keep_float_text {
+                Some(HashMap::new())
+            } else {
+                None
+            },
+            counts: HashMap::new(),
+            mask: options.sections,
+            normalize_keys: options.normalize_keys,
+            strict_sections: options.strict_sections,
+            new_world: false,
+            #[cfg(feature = "parallel")]
+            patch_records: None,
+        }
+    }
+
+    /// Interpret a `record` read by someone else.
+    pub(crate) fn feed(&mut self, record: StringRecord) -> Result<Option<Event>, ParseError> {
+        self.record = record;
+        self.read()
+    }
+
+    /// Interpret the current record.
+    fn read(&mut self) -> Result<Option<Event>, ParseError> {
+        match self.classify()? {
+            Step::Done(event) => Ok(event),
+            Step::Row => self.interpret(),
+        }
+    }
+
+    /// Follow sections and csv headers, the current record is either a
+    /// part of the file structure or a data row.
+    fn classify(&mut self) -> Result<Step, ParseError> {
+        // Hand-edited files may have whitespace-only lines
+        if self.record.iter().all(|field| field.trim().is_empty()) {
+            return Ok(Step::Done(None));
+        }
+
+        // Batch runs may concatenate several exports
+        if !self.sections.is_empty() && starts_world(&self.record) {
+            let event = self.flush();
+            self.new_world = true;
+            self.section = Section::Header;
+            self.headers = None;
+            if self.record.deserialize::<Section>(None).is_err() {
+                self.header.push(self.record.iter().collect());
+            }
+            return Ok(Step::Done(event));
+        }
+
+        // First check if we are looking on a new section
+        if let Ok(section) = self.record.deserialize::<Section>(None) {
+            let event = self.flush();
+            if !self.sections.contains(&section) {
+                self.sections.push(section);
+            }
+            self.section = section;
+            self.headers = None; // reset header
+            return Ok(Step::Done(event));
+        }
+
+        // Plot and extension names look like headings too
+        let has_names = matches!(self.section, Section::Plots | Section::Extensions);
+        if !has_names && is_name(&self.record) {
+            if self.strict_sections {
+                return Err(ParseError::UnexpectedSection(self.record[0].to_string()));
+            }
+            let event = self.flush();
+            self.unknown = Some((self.record[0].to_string(), vec![]));
+            return Ok(Step::Done(event));
+        }
+        if let Some((_, rows)) = self.unknown.as_mut() {
+            rows.push(self.record.iter().map(String::from).collect());
+            return Ok(Step::Done(None));
+        }
+
+        if !self.mask.contains(self.section) {
+            return Ok(Step::Done(None));
+        }
+
+        // No header? Read one. Breeds with different own-variables may
+        // start with a header of their own in the middle of a section.
+        let new_header = match (&self.headers, self.record.get(0)) {
+            // Stored headers are already lowercased with `normalize_keys`
+            (Some(headers), Some(first)) if self.normalize_keys => {
+                headers.get(0) == Some(first.to_lowercase().as_str())
+            }
+            (Some(headers), first) => headers.get(0) == first,
+            (None, _) => true,
+        };
+        if self.section.has_headers() && new_header {
+            let fields = self.fields.entry(self.section).or_default();
+            for field in &self.record {
+                if !fields.iter().any(|known| known == field) {
+                    fields.push(field.to_string());
+                }
+            }
+            self.headers = Some(section_headers(&self.record, self.normalize_keys));
+            return Ok(Step::Done(None));
+        }
+
+        let count = self.counts.entry(self.section).or_default();
+        *count += 1;
+        if let (Some(texts), Some(headers)) = (self.float_texts.as_mut(), &self.headers) {
+            // Only the last globals are kept
+            let row = match self.section {
+                Section::Globals => 0,
+                _ => *count - 1,
+            };
+            let columns = headers.iter().zip(&self.record);
+            let columns: Vec<_> = columns.filter_map(float_text).collect();
+            if !columns.is_empty() {
+                texts.insert((self.section, row), columns);
+            }
+        }
+
+        Ok(Step::Row)
+    }
+
+    /// Turn the current data row into an event.
+    fn interpret(&mut self) -> Result<Option<Event>, ParseError> {
+        #[cfg(feature = "parallel")]
+        if let (Section::Patches, Some(groups)) = (self.section, self.patch_records.as_mut()) {
+            // Repeated sections may order their columns differently
+            let headers = self.headers.clone().unwrap_or_default();
+            match groups.last_mut() {
+                Some((last, records)) if *last == headers => records.push(self.record.clone()),
+                _ => groups.push((headers, vec![self.record.clone()])),
+            }
+            return Ok(None);
+        }
+
+        let section = &self.section;
+        let record = &self.record;
+        let headers = self.headers.as_ref();
+        macro_rules! agent {
+            ($ty:ty) => {{
+                #[allow(unused_mut)]
+                let mut agent: $ty = deserialize(section, record, headers)?;
+                #[cfg(feature = "custom-fields")]
+                unshadow(&mut agent.custom);
+                agent
+            }};
+        }
+        let event = match section {
+            Section::RandomState => {
+                // Values are either in a single field or one per field
+                let fields: Vec<&str> = record.iter().collect();
+                let state =
+                    fields
+                        .join(" ")
+                        .parse()
+                        .map_err(|message| ParseError::InvalidRandomState {
+                            line: record.position().map_or(0, |pos| pos.line()),
+                            message,
+                        })?;
+                Event::RandomState(state)
+            }
+            Section::Globals => Event::Globals(agent!(Globals)),
+            Section::Clock => {
+                let clock: Clock = deserialize(section, record, headers)?;
+                Event::Ticks(clock.ticks)
+            }
+            Section::Turtles => Event::Turtle(agent!(Turtle)),
+            Section::Patches => Event::Patch(agent!(Patch)),
+            Section::Links => Event::Link(agent!(Link)),
+            Section::Output => Event::Output(parse_output(deserialize(section, record, headers)?)),
+            Section::Extensions => {
+                if is_name(record) {
+                    let extension = Extension {
+                        name: record[0].to_string(),
+                        rows: vec![],
+                    };
+                    return Ok(self.extension.replace(extension).map(Event::Extension));
+                }
+                if let Some(extension) = self.extension.as_mut() {
+                    extension
+                        .rows
+                        .push(record.iter().map(String::from).collect());
+                }
+                return Ok(None);
+            }
+            Section::Plots => {
+                if let Some(plot) = self.plots.read(record)? {
+                    return Ok(Some(Event::Plot(plot)));
+                }
+                return Ok(self.plots.current.take().map(Event::CurrentPlot));
+            }
+            Section::Header => {
+                self.header.push(record.iter().collect());
+                return Ok(None);
+            }
+        };
+        Ok(Some(event))
+    }
+
+    /// Finish the current section returning its pending data if any.
+    pub(crate) fn flush(&mut self) -> Option<Event> {
+        if !self.header.is_empty() {
+            let lines = std::mem::take(&mut self.header);
+            return Some(Event::Header(Header::from_lines(lines)));
+        }
+        if let Some(plot) = self.plots.finish() {
+            return Some(Event::Plot(plot));
+        }
+        if let Some(extension) = self.extension.take() {
+            return Some(Event::Extension(extension));
+        }
+        self.unknown
+            .take()
+            .map(|(name, rows)| Event::UnknownSection { name, rows })
+    }
+}
+
+/// What [`State::classify`] found in a record.
+// As large as `Option<Event>` returned for every record anyway
+#[allow(clippy::large_enum_variant)]
+enum Step {
+    /// A heading, a csv header or a row handled on the spot, maybe
+    /// finishing an event.
+    Done(Option<Event>),
+    /// A data row of the current section.
+    Row,
+}
+
+/// Original text of floats which format differently by section and
+/// row, the rows hold column names and texts.
+pub(crate) type FloatTexts = HashMap<(Section, usize), Vec<(String, String)>>;
+
+/// Column name and text of a float `field` if it formats differently.
+fn float_text((name, field): (&str, &str)) -> Option<(String, String)> {
+    if field.parse::<i64>().is_ok() {
+        return None;
+    }
+    match field.parse::<f64>() {
+        Ok(v) if v.to_string() != field => Some((name.to_string(), field.to_string())),
+        _ => None,
+    }
+}
+
+/// Prefix of repeated column names, can't be a part of NetLogo names.
+const SHADOWED: char = '\u{1}';
+
+/// Make headers to deserialize rows of a section by from a header
+/// `record`, lowercased with `normalize_keys`.
+///
+/// Only the first of repeated columns goes to a typed field, e.g. a
+/// user global named `ticks` after the built-in one. The rest are
+/// renamed here and get their names back in custom fields in
+/// [`unshadow`], or are skipped without the "custom-fields" feature.
+pub(crate) fn section_headers(record: &StringRecord, normalize_keys: bool) -> StringRecord {
+    let mut headers = StringRecord::new();
+    for field in record {
+        let field = if normalize_keys {
+            field.to_lowercase()
+        } else {
+            field.to_string()
+        };
+        if headers.iter().any(|seen| seen == field) {
+            headers.push_field(&format!("{}{}", SHADOWED, field));
+        } else {
+            headers.push_field(&field);
+        }
+    }
+    headers
+}
+
+/// Give repeated columns renamed by [`section_headers`] their names
+/// back, the last one wins if there are several.
+#[cfg(feature = "custom-fields")]
+pub(crate) fn unshadow(custom: &mut IndexMap<String, Value>) {
+    if !custom.keys().any(|name| name.starts_with(SHADOWED)) {
+        return;
+    }
+    *custom = std::mem::take(custom)
+        .into_iter()
+        .map(|(name, value)| (unshadowed(&name).to_string(), value))
+        .collect();
+}
+
+/// Name of a column renamed by [`section_headers`].
+#[cfg(any(feature = "std", feature = "custom-fields"))]
+pub(crate) fn unshadowed(name: &str) -> &str {
+    name.trim_start_matches(SHADOWED)
+}
+
+/// Whether a `record` is the first line of an export or a "HEADER"
+/// heading.
+fn starts_world(record: &StringRecord) -> bool {
+    record.len() == 1
+        && (record[0].starts_with("export-world data") || &record[0] == Section::Header.name())
+}
+
+/// Row of a legacy "CLOCK" section.
+#[derive(Deserialize)]
+struct Clock {
+    #[serde(alias = "clock")]
+    ticks: f64,
+}
+
+/// Make csv reader of `.dat` files.
+///
+/// Windows exports may start with a UTF-8 BOM and end lines with
+/// `\r\n`, csv strips the BOM and takes any of `\r`, `\n` or `\r\n`
+/// as a line end itself, so headings never keep a carriage return.
+#[cfg(feature = "std")]
+fn csv_reader<R: Read>(reader: R, options: &ParseOptions) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .comment(options.comment)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader)
+}
+
+/// Iterator over events of a [`WorldReader`].
+#[cfg(feature = "std")]
+pub struct Events<'a, R> {
+    reader: &'a mut WorldReader<R>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, R: Read> Iterator for Events<'a, R> {
+    type Item = Result<Event, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_event().transpose()
+    }
+}
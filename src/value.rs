@@ -1,22 +1,474 @@
 //! Value type for custom fields.
 
-use serde::Deserialize;
+use crate::de::{parse_bool, quote, unescape, unquote};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
 use std::convert::TryFrom;
+use std::fmt;
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
-#[serde(rename_all = "snake_case")]
-#[serde(untagged)]
+/// A value of a custom field.
+///
+/// Non-negative integers are parsed as [`Value::U64`] and negative ones
+/// as [`Value::I64`], use [`Value::as_integer`] to treat them the same.
+#[derive(Debug, PartialEq, Clone)]
 pub enum Value {
     Bool(bool),
     U64(u64),
     I64(i64),
+    /// Float, NetLogo's `NaN` and `Infinity` of undefined variables
+    /// are parsed too.
+    ///
+    /// `NaN` isn't equal to itself, so two values holding it never
+    /// compare equal, check [`f64::is_nan`] instead.
     Float(f64),
     String(String),
+    /// NetLogo list like `[1 2 3]`.
+    List(Vec<Value>),
+    /// NetLogo `nobody`, i.e. a missing agent.
+    Nobody,
+    /// Reference to an agent like `{turtle 3}` or `{patch 1 2}`.
+    AgentRef {
+        kind: String,
+        id: Vec<i64>,
+    },
+}
+
+impl Value {
+    /// Whether the value is NetLogo's `nobody`.
+    pub fn is_nobody(&self) -> bool {
+        *self == Value::Nobody
+    }
+
+    /// Get any number as `f64`.
+    ///
+    /// NetLogo doesn't distinguish integers from floats, so `6` and
+    /// `6.0` are the same.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::U64(v) => Some(v as f64),
+            Value::I64(v) => Some(v as f64),
+            Value::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get any number as `f64` like [`Value::as_f64`] also parsing
+    /// numeric strings, e.g. `"6"` of a variable quoted by mistake.
+    ///
+    /// ```
+    /// use netlogo_world_parser::Value;
+    ///
+    /// let six = Value::String("6".into());
+    /// assert_eq!(six.as_f64(), None);
+    /// assert_eq!(six.as_f64_lenient(), Some(6.0));
+    /// ```
+    pub fn as_f64_lenient(&self) -> Option<f64> {
+        match self {
+            Value::String(string) => string.trim().parse().ok(),
+            value => value.as_f64(),
+        }
+    }
+
+    /// Get an integer as `i64` regardless of its sign.
+    ///
+    /// Unlike [`Value::as_i64`] floats are never accepted.
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Value::U64(v) => i64::try_from(v).ok(),
+            Value::I64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get an integral number as `i64`, floats are accepted when they
+    /// have no fractional part.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::U64(v) => i64::try_from(v).ok(),
+            Value::I64(v) => Some(v),
+            Value::Float(v) if v.fract() == 0.0 && v.abs() < i64::MAX as f64 => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    /// Get a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Compare any number with `other`, `None` for non-numbers and
+    /// `NaN`.
+    ///
+    /// ```
+    /// use netlogo_world_parser::Value;
+    /// use std::cmp::Ordering;
+    ///
+    /// assert_eq!(Value::U64(6).partial_cmp_f64(5.0), Some(Ordering::Greater));
+    /// ```
+    pub fn partial_cmp_f64(&self, other: f64) -> Option<Ordering> {
+        self.as_f64()?.partial_cmp(&other)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Formats a value the way NetLogo prints it with `write`, e.g.
+/// `[1 "two" nobody]`.
+///
+/// Strings are quoted and escaped.
+///
+/// ```
+/// use netlogo_world_parser::Value;
+///
+/// let list = Value::List(vec![Value::U64(1), Value::String("two".into()), Value::Nobody]);
+/// assert_eq!(list.to_string(), r#"[1 "two" nobody]"#);
+/// ```
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::Float(v) if v.is_infinite() => {
+                let sign = if v.is_sign_negative() { "-" } else { "" };
+                write!(f, "{}Infinity", sign)
+            }
+            Value::Float(v) => write!(f, "{}", v),
+            Value::String(v) => f.write_str(&quote(v)),
+            Value::List(list) => {
+                f.write_str("[")?;
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Value::Nobody => f.write_str("nobody"),
+            Value::AgentRef { kind, id } => f.write_str(&agent_ref(kind, id)),
+        }
+    }
+}
+
+/// Values are serialized as plain scalars and sequences, `nobody`
+/// becomes a unit, i.e. `null` in JSON.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::U64(v) => serializer.serialize_u64(*v),
+            Value::I64(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f64(*v),
+            // As a literal, so strings like `"true"` read back as strings
+            Value::String(v) => serializer.collect_str(&quote(v)),
+            Value::List(list) => list.serialize(serializer),
+            Value::Nobody => serializer.serialize_unit(),
+            Value::AgentRef { kind, id } => serializer.collect_str(&agent_ref(kind, id)),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a NetLogo value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::I64(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::U64(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        if is_quoted(v) {
+            return Ok(Value::String(unquote(v)));
+        }
+        if v == "nobody" {
+            return Ok(Value::Nobody);
+        }
+        if let Some(v) = parse_bool(v) {
+            return Ok(Value::Bool(v));
+        }
+        if let Some(agent) = parse_agent_ref(v) {
+            return Ok(agent);
+        }
+        Ok(parse_list(v).unwrap_or_else(|| Value::String(v.to_string())))
+    }
+
+    // Serialized `nobody` and lists
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nobody)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut list = vec![];
+        while let Some(item) = seq.next_element()? {
+            list.push(item);
+        }
+        Ok(Value::List(list))
+    }
+}
+
+/// A value borrowing its strings from a record being read.
+///
+/// Unlike [`Value`] strings aren't copied, so fields can be inspected
+/// without allocations, e.g. with [`csv::StringRecord::deserialize`].
+/// Lists are kept as literals, use [`ValueRef::to_value`] to parse them.
+///
+/// ```
+/// use netlogo_world_parser::ValueRef;
+///
+/// assert_eq!(ValueRef::parse("\"bug\""), ValueRef::String("\"bug\""));
+/// assert_eq!(ValueRef::parse("-2"), ValueRef::I64(-2));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValueRef<'a> {
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    Float(f64),
+    String(&'a str),
+    /// NetLogo list literal like `[1 2 3]`.
+    List(&'a str),
+    Nobody,
+    AgentRef {
+        kind: &'a str,
+        id: Vec<i64>,
+    },
+}
+
+impl<'a> ValueRef<'a> {
+    /// Interpret a `field` the same way [`Value`] does.
+    pub fn parse(field: &'a str) -> Self {
+        if field == "nobody" {
+            ValueRef::Nobody
+        } else if let Some(v) = parse_bool(field) {
+            ValueRef::Bool(v)
+        } else if let Ok(v) = field.parse() {
+            ValueRef::U64(v)
+        } else if let Ok(v) = field.parse() {
+            ValueRef::I64(v)
+        } else if let Ok(v) = field.parse() {
+            ValueRef::Float(v)
+        } else if let Some((kind, id)) = split_agent_ref(field) {
+            ValueRef::AgentRef { kind, id }
+        } else if parse_list(field).is_some() {
+            ValueRef::List(field)
+        } else {
+            ValueRef::String(field)
+        }
+    }
+
+    /// Make an owned [`Value`] parsing lists.
+    pub fn to_value(&self) -> Value {
+        match self {
+            ValueRef::Bool(v) => Value::Bool(*v),
+            ValueRef::U64(v) => Value::U64(*v),
+            ValueRef::I64(v) => Value::I64(*v),
+            ValueRef::Float(v) => Value::Float(*v),
+            ValueRef::String(v) => Value::String(unquote(v)),
+            ValueRef::List(v) => parse_list(v).unwrap_or_else(|| Value::String(v.to_string())),
+            ValueRef::Nobody => Value::Nobody,
+            ValueRef::AgentRef { kind, id } => Value::AgentRef {
+                kind: kind.to_string(),
+                id: id.clone(),
+            },
+        }
+    }
+}
+
+/// Fields are borrowed, so only deserializers which can lend their
+/// input like csv records are supported.
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(ValueRefVisitor)
+    }
+}
+
+struct ValueRefVisitor;
+
+impl<'de> Visitor<'de> for ValueRefVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a borrowed NetLogo value")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::parse(v))
+    }
+}
+
+/// Parse a NetLogo list literal.
+///
+/// Returns `None` if `input` is not a well-formed list.
+pub(crate) fn parse_list(input: &str) -> Option<Value> {
+    let input = input.trim();
+    if !input.starts_with('[') {
+        return None;
+    }
+    let mut parser = Parser { rest: input };
+    let list = parser.value()?;
+    if parser.rest.trim().is_empty() {
+        Some(list)
+    } else {
+        None
+    }
+}
+
+/// Parse an agent reference like `{turtle 3}`.
+///
+/// Agentsets like `{all-turtles}` or `{breed ants}` aren't agents and
+/// give `None`.
+fn parse_agent_ref(input: &str) -> Option<Value> {
+    let (kind, id) = split_agent_ref(input)?;
+    Some(Value::AgentRef {
+        kind: kind.to_string(),
+        id,
+    })
+}
+
+/// Split an agent reference into its kind and id.
+fn split_agent_ref(input: &str) -> Option<(&str, Vec<i64>)> {
+    let inner = input.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut tokens = inner.split_whitespace();
+    let kind = tokens.next()?;
+    let id = tokens
+        .map(|token| token.parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    if id.is_empty() {
+        return None;
+    }
+    Some((kind, id))
+}
+
+/// Format an agent reference as a NetLogo literal.
+fn agent_ref(kind: &str, id: &[i64]) -> String {
+    let id: Vec<String> = id.iter().map(i64::to_string).collect();
+    format!("{{{} {}}}", kind, id.join(" "))
+}
+
+/// Recursive descent parser of NetLogo literals.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self) -> Option<Value> {
+        self.rest = self.rest.trim_start();
+        if let Some(rest) = self.rest.strip_prefix('[') {
+            self.rest = rest;
+            self.list()
+        } else if self.rest.starts_with('"') {
+            self.string()
+        } else if self.rest.starts_with('{') {
+            self.agent_ref()
+        } else {
+            self.scalar()
+        }
+    }
+
+    /// Parse list elements after the opening bracket.
+    fn list(&mut self) -> Option<Value> {
+        let mut list = vec![];
+        loop {
+            self.rest = self.rest.trim_start();
+            if let Some(rest) = self.rest.strip_prefix(']') {
+                self.rest = rest;
+                return Some(Value::List(list));
+            }
+            if self.rest.is_empty() {
+                return None;
+            }
+            list.push(self.value()?);
+        }
+    }
+
+    fn string(&mut self) -> Option<Value> {
+        let mut escaped = false;
+        for (i, c) in self.rest.char_indices().skip(1) {
+            match c {
+                '\\' => escaped = !escaped,
+                '"' if !escaped => {
+                    let string = unescape(&self.rest[1..i]);
+                    self.rest = &self.rest[i + 1..];
+                    return Some(Value::String(string));
+                }
+                _ => escaped = false,
+            }
+        }
+        None
+    }
+
+    fn agent_ref(&mut self) -> Option<Value> {
+        let end = self.rest.find('}')? + 1;
+        let literal = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        Some(parse_agent_ref(literal).unwrap_or_else(|| Value::String(literal.to_string())))
+    }
+
+    fn scalar(&mut self) -> Option<Value> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || c == '[' || c == ']')
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return None;
+        }
+        let token = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        Some(parse_scalar(token))
+    }
+}
+
+/// Whether `field` is a NetLogo string literal like `"bug"`.
+fn is_quoted(field: &str) -> bool {
+    field.len() >= 2 && field.starts_with('"') && field.ends_with('"')
+}
+
+/// Interpret a bare token the same way csv would.
+fn parse_scalar(token: &str) -> Value {
+    if let Some(v) = parse_bool(token) {
+        Value::Bool(v)
+    } else if let Ok(v) = token.parse() {
+        Value::U64(v)
+    } else if let Ok(v) = token.parse() {
+        Value::I64(v)
+    } else if let Ok(v) = token.parse() {
+        Value::Float(v)
+    } else if token == "nobody" {
+        Value::Nobody
+    } else {
+        Value::String(token.to_string())
+    }
 }
 
 /// Allow convection to a desired type via `try_into`.
 macro_rules! impl_value_try_from {
-    ($from:path, $to:path) => {
+    ($from:path, $to:ty) => {
         impl TryFrom<Value> for $to {
             type Error = String;
 
@@ -27,6 +479,17 @@ macro_rules! impl_value_try_from {
                 }
             }
         }
+
+        impl TryFrom<&Value> for $to {
+            type Error = String;
+
+            fn try_from(value: &Value) -> Result<Self, Self::Error> {
+                match value {
+                    $from(x) => Ok(x.clone()),
+                    _ => Err(format!("Expected '{}' got {:?}", stringify!($from), value)),
+                }
+            }
+        }
     };
 }
 
@@ -35,3 +498,255 @@ impl_value_try_from!(Value::U64, u64);
 impl_value_try_from!(Value::I64, i64);
 impl_value_try_from!(Value::Float, f64);
 impl_value_try_from!(Value::String, String);
+impl_value_try_from!(Value::List, Vec<Value>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::StringRecord;
+    use std::collections::HashMap;
+
+    fn value(field: &str) -> Value {
+        StringRecord::from(vec![field])
+            .deserialize(None)
+            .expect("deserialize failed")
+    }
+
+    #[test]
+    fn lenient_numbers() {
+        assert_eq!(value("\"6\"").as_f64_lenient(), Some(6.0));
+        assert_eq!(value("\" 0.5 \"").as_f64_lenient(), Some(0.5));
+        assert_eq!(value("6").as_f64_lenient(), Some(6.0));
+        assert_eq!(value("\"bug\"").as_f64_lenient(), None);
+        assert_eq!(value("[1]").as_f64_lenient(), None);
+        assert_eq!(value("\"6\"").as_f64(), None);
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(value("true"), Value::Bool(true));
+        assert_eq!(value("6"), Value::U64(6));
+        assert_eq!(value("-6"), Value::I64(-6));
+        assert_eq!(value("0.5"), Value::Float(0.5));
+        assert_eq!(value("\"bug\""), Value::String("bug".into()));
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(value("1.5E-7"), Value::Float(1.5e-7));
+        assert_eq!(value("2e10"), Value::Float(2e10));
+        assert_eq!(value("-3.0E+4"), Value::Float(-3.0e4));
+        assert_eq!(
+            value("[1.5E-7 -3.0E+4]"),
+            Value::List(vec![Value::Float(1.5e-7), Value::Float(-3.0e4)])
+        );
+    }
+
+    #[test]
+    fn special_floats() {
+        // NaN isn't equal to itself
+        assert!(matches!(value("NaN"), Value::Float(v) if v.is_nan()));
+        assert_eq!(value("Infinity"), Value::Float(f64::INFINITY));
+        assert_eq!(value("-Infinity"), Value::Float(f64::NEG_INFINITY));
+        match value("[NaN -Infinity]") {
+            Value::List(list) => match list.as_slice() {
+                [Value::Float(nan), Value::Float(inf)] => {
+                    assert!(nan.is_nan());
+                    assert_eq!(*inf, f64::NEG_INFINITY);
+                }
+                list => panic!("unexpected list: {:?}", list),
+            },
+            value => panic!("unexpected value: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn borrowed_values() {
+        let headers = StringRecord::from(vec!["shape", "energy", "path"]);
+        let record = StringRecord::from(vec!["\"bug\"", "4.5", "[1 2]"]);
+        let fields: HashMap<&str, ValueRef> = record
+            .deserialize(Some(&headers))
+            .expect("deserialize failed");
+        match fields["shape"] {
+            ValueRef::String(shape) => {
+                assert_eq!(shape, "\"bug\"");
+                assert!(std::ptr::eq(shape, &record[0]));
+            }
+            ref value => panic!("unexpected value: {:?}", value),
+        }
+        assert_eq!(fields["energy"], ValueRef::Float(4.5));
+        assert_eq!(fields["path"], ValueRef::List("[1 2]"));
+        assert_eq!(fields["path"].to_value(), value("[1 2]"));
+        assert_eq!(
+            ValueRef::parse("{turtle 3}").to_value(),
+            value("{turtle 3}")
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::U64(6).to_string(), "6");
+        assert_eq!(Value::I64(-2).to_string(), "-2");
+        assert_eq!(Value::Float(6.0).to_string(), "6");
+        assert_eq!(Value::Float(0.25).to_string(), "0.25");
+        assert_eq!(Value::Float(f64::NEG_INFINITY).to_string(), "-Infinity");
+        assert_eq!(Value::Float(f64::NAN).to_string(), "NaN");
+        assert_eq!(
+            Value::String("say \"hi\"".into()).to_string(),
+            r#""say \"hi\"""#
+        );
+        assert_eq!(Value::Nobody.to_string(), "nobody");
+        assert_eq!(
+            Value::AgentRef {
+                kind: "patch".into(),
+                id: vec![1, -2]
+            }
+            .to_string(),
+            "{patch 1 -2}"
+        );
+        assert_eq!(Value::List(vec![]).to_string(), "[]");
+        let list = value(r#"[1 [2.5 "a b"] nobody {turtle 3}]"#);
+        assert_eq!(list.to_string(), r#"[1 [2.5 "a b"] nobody {turtle 3}]"#);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            value("[1 -2 3.5]"),
+            Value::List(vec![Value::U64(1), Value::I64(-2), Value::Float(3.5)])
+        );
+        assert_eq!(
+            value(r#"["a" "b \"c\""]"#),
+            Value::List(vec![
+                Value::String("a".into()),
+                Value::String("b \"c\"".into())
+            ])
+        );
+        assert_eq!(value("[]"), Value::List(vec![]));
+    }
+
+    #[test]
+    fn nested_lists() {
+        let list = value("[[1 2] [3 4]]");
+        assert_eq!(
+            list,
+            Value::List(vec![
+                Value::List(vec![Value::U64(1), Value::U64(2)]),
+                Value::List(vec![Value::U64(3), Value::U64(4)]),
+            ])
+        );
+        let rows = Vec::<Value>::try_from(list).expect("not a list");
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn borrowed_conversions() {
+        let number = Value::U64(6);
+        assert_eq!(u64::try_from(&number), Ok(6));
+        assert!(bool::try_from(&number).is_err());
+        let string = Value::String("bug".into());
+        assert_eq!(String::try_from(&string), Ok("bug".to_string()));
+        assert_eq!(string, Value::String("bug".into()));
+    }
+
+    #[test]
+    fn capitalized_bools() {
+        assert_eq!(value("True"), Value::Bool(true));
+        assert_eq!(value("FALSE"), Value::Bool(false));
+        assert_eq!(
+            value("[True false]"),
+            Value::List(vec![Value::Bool(true), Value::Bool(false)])
+        );
+        assert_eq!(value("\"True\""), Value::String("True".into()));
+    }
+
+    #[test]
+    fn nobody() {
+        assert!(value("nobody").is_nobody());
+        assert_eq!(
+            value("[nobody 1]"),
+            Value::List(vec![Value::Nobody, Value::U64(1)])
+        );
+        assert_eq!(value("\"nobody\""), Value::String("nobody".into()));
+    }
+
+    #[test]
+    fn agent_refs() {
+        assert_eq!(
+            value("{turtle 3}"),
+            Value::AgentRef {
+                kind: "turtle".into(),
+                id: vec![3]
+            }
+        );
+        assert_eq!(
+            value("{patch 1 -2}"),
+            Value::AgentRef {
+                kind: "patch".into(),
+                id: vec![1, -2]
+            }
+        );
+        assert_eq!(
+            value("[{link 0 1} nobody]"),
+            Value::List(vec![
+                Value::AgentRef {
+                    kind: "link".into(),
+                    id: vec![0, 1]
+                },
+                Value::Nobody
+            ])
+        );
+        assert_eq!(
+            value("{all-turtles}"),
+            Value::String("{all-turtles}".into())
+        );
+        assert_eq!(value("{breed ants}"), Value::String("{breed ants}".into()));
+    }
+
+    #[test]
+    fn numeric_coercion() {
+        for v in &[Value::U64(6), Value::I64(6), Value::Float(6.0)] {
+            assert_eq!(v.as_f64(), Some(6.0));
+            assert_eq!(v.as_i64(), Some(6));
+        }
+        assert_eq!(Value::I64(-3).as_f64(), Some(-3.0));
+        assert_eq!(Value::Float(6.5).as_i64(), None);
+        assert_eq!(Value::U64(u64::MAX).as_i64(), None);
+        assert_eq!(Value::String("6".into()).as_f64(), None);
+        assert_eq!(Value::Bool(true).as_f64(), None);
+    }
+
+    #[test]
+    fn compare_numbers() {
+        assert_eq!(Value::U64(6).partial_cmp_f64(5.0), Some(Ordering::Greater));
+        assert_eq!(Value::I64(-6).partial_cmp_f64(5.0), Some(Ordering::Less));
+        assert_eq!(
+            Value::Float(5.0).partial_cmp_f64(5.0),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(Value::Float(f64::NAN).partial_cmp_f64(5.0), None);
+        assert_eq!(Value::String("6".into()).partial_cmp_f64(5.0), None);
+        assert_eq!(Value::Bool(true).partial_cmp_f64(1.0), None);
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(value("3").as_integer(), Some(3));
+        assert_eq!(value("-3").as_integer(), Some(-3));
+        assert_eq!(value("3.0").as_integer(), None);
+        assert_eq!(Value::U64(u64::MAX).as_integer(), None);
+    }
+
+    #[test]
+    fn bool_coercion() {
+        assert_eq!(Value::Bool(false).as_bool(), Some(false));
+        assert_eq!(Value::U64(1).as_bool(), None);
+    }
+
+    #[test]
+    fn malformed_lists_are_strings() {
+        assert_eq!(value("[1 2"), Value::String("[1 2".into()));
+        assert_eq!(value("[1] 2"), Value::String("[1] 2".into()));
+    }
+}
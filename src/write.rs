@@ -0,0 +1,401 @@
+//! Writing worlds back in `.dat` format.
+//!
+//! NetLogo quotes every field and writes strings as NetLogo literals,
+//! e.g. `"""bug"""`, so typed string fields are quoted again on the
+//! way out. Custom fields are written after the built-in ones.
+
+use crate::de::quote;
+use crate::reader::{unshadowed, FloatTexts};
+#[cfg(feature = "custom-fields")]
+use crate::Value;
+use crate::{Globals, Link, NetLogoWorld, Patch, Section, Turtle};
+use std::io::{self, Write};
+
+/// Version written when a world has no header.
+const VERSION: &str = "6.1.0";
+
+/// Write NetLogo world to a writer in `.dat` format.
+pub fn write(world: &NetLogoWorld, writer: impl Write) -> io::Result<()> {
+    let mut wtr = Writer {
+        inner: writer,
+        float_texts: &world.info.float_texts,
+    };
+
+    match &world.header {
+        Some(header) => {
+            wtr.record(&[format!("export-world data (NetLogo {})", header.version)])?;
+            if let Some(model) = &header.model {
+                wtr.record(&[model])?;
+            }
+            wtr.record(&[&header.exported_at])?;
+        }
+        None => wtr.record(&[format!("export-world data (NetLogo {})", VERSION)])?,
+    }
+    wtr.blank()?;
+
+    if let Some(random_state) = &world.random_state {
+        wtr.heading(Section::RandomState)?;
+        wtr.record(&[random_state.to_string()])?;
+        wtr.blank()?;
+    }
+
+    // Default bounds would make up a world size for exports without it
+    if world.sections_seen().contains(&Section::Globals) || world.globals != Globals::default() {
+        wtr.heading(Section::Globals)?;
+        write_rows(
+            &mut wtr,
+            Section::Globals,
+            std::slice::from_ref(&world.globals),
+        )?;
+        wtr.blank()?;
+    }
+
+    wtr.heading(Section::Turtles)?;
+    write_rows(&mut wtr, Section::Turtles, &world.turtles)?;
+    wtr.blank()?;
+
+    wtr.heading(Section::Patches)?;
+    write_rows(&mut wtr, Section::Patches, &world.patches)?;
+    wtr.blank()?;
+
+    wtr.heading(Section::Links)?;
+    write_rows(&mut wtr, Section::Links, &world.links)?;
+    wtr.blank()?;
+
+    wtr.heading(Section::Output)?;
+    if !world.output.is_empty() {
+        wtr.record(&[quote(&world.output.join("\n"))])?;
+    }
+
+    // Before plots and extensions which have such names too
+    for (name, rows) in &world.unknown_sections {
+        wtr.record(&[name])?;
+        for row in rows {
+            wtr.record(row)?;
+        }
+        wtr.blank()?;
+    }
+
+    wtr.heading(Section::Plots)?;
+    write_plots(&mut wtr, world)?;
+
+    wtr.heading(Section::Extensions)?;
+    for extension in &world.extensions {
+        wtr.blank()?;
+        wtr.record(&[&extension.name])?;
+        for row in &extension.rows {
+            wtr.record(row)?;
+        }
+    }
+
+    wtr.inner.flush()
+}
+
+/// Section with a csv header followed by a row per item.
+trait Row {
+    /// Built-in columns in NetLogo order.
+    fn fields(&self) -> Vec<(&'static str, String)>;
+
+    /// Custom fields as NetLogo literals.
+    fn custom(&self) -> Vec<(&str, String)> {
+        vec![]
+    }
+}
+
+/// Rows with other custom fields, e.g. of another breed, start with a
+/// header of their own like NetLogo writes for breeds.
+fn write_rows<W: Write, R: Row>(
+    wtr: &mut Writer<'_, W>,
+    section: Section,
+    rows: &[R],
+) -> io::Result<()> {
+    let first = match rows.first() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+    let builtin: Vec<&str> = first.fields().into_iter().map(|(name, _)| name).collect();
+    let mut custom: Option<Vec<&str>> = None;
+
+    for (i, row) in rows.iter().enumerate() {
+        let mut fields = row.custom();
+        let same = custom.as_ref().is_some_and(|custom| {
+            custom.len() == fields.len() && fields.iter().all(|(name, _)| custom.contains(name))
+        });
+        if !same {
+            let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+            wtr.record(builtin.iter().chain(&names))?;
+            custom = Some(names);
+        }
+        let custom = custom.as_deref().unwrap_or_default();
+
+        let mut values: Vec<String> = row.fields().into_iter().map(|(_, v)| v).collect();
+        for name in custom {
+            let value = fields
+                .iter()
+                .position(|(field, _)| field == name)
+                .map(|i| fields.swap_remove(i).1)
+                .unwrap_or_default();
+            values.push(value);
+        }
+        if let Some(texts) = wtr.float_texts.get(&(section, i)) {
+            let (builtin_values, custom_values) = values.split_at_mut(builtin.len());
+            for (name, value) in builtin.iter().zip(builtin_values) {
+                let text = texts.iter().find(|(column, _)| column == name);
+                restore_float(text, value);
+            }
+            // Repeated columns are renamed by the reader and the last one
+            // wins, the first one goes to a built-in field of that name
+            for (name, value) in custom.iter().zip(custom_values) {
+                let shadowed = builtin.contains(name);
+                let text = texts.iter().rev().find(|(column, _)| {
+                    unshadowed(column) == *name && (!shadowed || column != name)
+                });
+                restore_float(text, value);
+            }
+        }
+        wtr.record(&values)?;
+    }
+    Ok(())
+}
+
+fn write_plots<W: Write>(wtr: &mut Writer<'_, W>, world: &NetLogoWorld) -> io::Result<()> {
+    wtr.record(&[&world.current_plot])?;
+    for plot in &world.plots {
+        wtr.record(&[quote(&plot.name)])?;
+        wtr.record(&[
+            "x min",
+            "x max",
+            "y min",
+            "y max",
+            "autoplot?",
+            "current pen",
+            "legend open?",
+            "number of pens",
+        ])?;
+        wtr.record(&[
+            plot.x_min.to_string(),
+            plot.x_max.to_string(),
+            plot.y_min.to_string(),
+            plot.y_max.to_string(),
+            plot.autoplot.to_string(),
+            quote(&plot.current_pen),
+            plot.legend_open.to_string(),
+            plot.pens.len().to_string(),
+        ])?;
+        wtr.blank()?;
+
+        wtr.record(&["pen name", "pen down?", "mode", "interval", "color", "x"])?;
+        for pen in &plot.pens {
+            wtr.record(&[
+                quote(&pen.name),
+                pen.pen_down.to_string(),
+                pen.pen_mode.to_string(),
+                pen.interval.to_string(),
+                pen.pen_color.to_string(),
+                pen.x.to_string(),
+            ])?;
+        }
+        wtr.blank()?;
+
+        if plot.pens.is_empty() {
+            continue;
+        }
+        let names = plot.pens.iter().flat_map(|pen| {
+            vec![
+                quote(&pen.name),
+                String::new(),
+                String::new(),
+                String::new(),
+            ]
+        });
+        wtr.record(names)?;
+        let headers = plot
+            .pens
+            .iter()
+            .flat_map(|_| vec!["x", "y", "color", "pen down?"]);
+        wtr.record(headers)?;
+        let points = plot.pens.iter().map(|pen| pen.points.len()).max();
+        for i in 0..points.unwrap_or(0) {
+            let row = plot.pens.iter().flat_map(|pen| match pen.points.get(i) {
+                Some(point) => vec![
+                    point.x.to_string(),
+                    point.y.to_string(),
+                    point.color.to_string(),
+                    point.pen_down.to_string(),
+                ],
+                None => vec![String::new(); 4],
+            });
+            wtr.record(row)?;
+        }
+        wtr.blank()?;
+    }
+    Ok(())
+}
+
+/// Minimal csv writer quoting every non-empty field like NetLogo does.
+struct Writer<'a, W> {
+    inner: W,
+    /// Original text of floats, see
+    /// [`ParseOptions::keep_float_text`](crate::ParseOptions::keep_float_text).
+    float_texts: &'a FloatTexts,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    fn record<I>(&mut self, fields: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.inner.write_all(b",")?;
+            }
+            let field = field.as_ref();
+            if !field.is_empty() {
+                write!(self.inner, "\"{}\"", field.replace('"', "\"\""))?;
+            }
+        }
+        self.inner.write_all(b"\n")
+    }
+
+    fn heading(&mut self, section: Section) -> io::Result<()> {
+        self.record(&[section.name()])
+    }
+
+    /// Write an empty line.
+    fn blank(&mut self) -> io::Result<()> {
+        self.inner.write_all(b"\n")
+    }
+}
+
+/// Put the original `text` of a float column back unless its `value`
+/// was changed.
+fn restore_float(text: Option<&(String, String)>, value: &mut String) {
+    let text = match text {
+        Some((_, text)) => text,
+        None => return,
+    };
+    let bits = |field: &str| field.parse::<f64>().map(f64::to_bits).ok();
+    if bits(text).is_some() && bits(text) == bits(value) {
+        *value = text.clone();
+    }
+}
+
+/// Make a string literal of a label, empty if there is none.
+fn label(label: &Option<String>) -> String {
+    quote(label.as_deref().unwrap_or_default())
+}
+
+#[cfg(feature = "custom-fields")]
+fn custom_fields<'a>(fields: impl Iterator<Item = (&'a str, &'a Value)>) -> Vec<(&'a str, String)> {
+    fields
+        .map(|(name, value)| (name, value.to_string()))
+        .collect()
+}
+
+impl Row for Globals {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("min-pxcor", self.min_pxcor.to_string()),
+            ("max-pxcor", self.max_pxcor.to_string()),
+            ("min-pycor", self.min_pycor.to_string()),
+            ("max-pycor", self.max_pycor.to_string()),
+            ("ticks", self.ticks.to_string()),
+        ];
+        if let Some(wrap) = self.wrapping_allowed_in_x {
+            fields.push(("wrapping-allowed-in-x", wrap.to_string()));
+        }
+        if let Some(wrap) = self.wrapping_allowed_in_y {
+            fields.push(("wrapping-allowed-in-y", wrap.to_string()));
+        }
+        fields
+    }
+
+    #[cfg(feature = "custom-fields")]
+    fn custom(&self) -> Vec<(&str, String)> {
+        custom_fields(self.custom_fields())
+    }
+}
+
+impl Row for Turtle {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("who", self.who.to_string()),
+            ("color", self.color.to_string()),
+            ("heading", self.heading.to_string()),
+            ("xcor", self.xcor.to_string()),
+            ("ycor", self.ycor.to_string()),
+            ("shape", quote(&self.shape)),
+            ("label", label(&self.label)),
+            ("label-color", self.label_color.to_string()),
+            ("breed", turtle_breed(&self.breed)),
+            ("hidden?", self.hidden.to_string()),
+            ("size", self.size.to_string()),
+            ("pen-size", self.pen_size.to_string()),
+            ("pen-mode", quote(&self.pen_mode)),
+        ]
+    }
+
+    #[cfg(feature = "custom-fields")]
+    fn custom(&self) -> Vec<(&str, String)> {
+        custom_fields(self.custom_fields())
+    }
+}
+
+/// Make a breed reference of a turtle.
+fn turtle_breed(breed: &str) -> String {
+    if breed == "turtles" {
+        "{all-turtles}".to_string()
+    } else {
+        format!("{{breed {}}}", breed)
+    }
+}
+
+/// Make a breed reference of a link.
+fn link_breed(breed: &str) -> String {
+    if breed == "links" {
+        "{links}".to_string()
+    } else {
+        format!("{{breed {}}}", breed)
+    }
+}
+
+impl Row for Patch {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("pxcor", self.pxcor.to_string()),
+            ("pycor", self.pycor.to_string()),
+            ("pcolor", self.pcolor.to_string()),
+            ("plabel", label(&self.plabel)),
+            ("plabel-color", self.plabel_color.to_string()),
+        ]
+    }
+
+    #[cfg(feature = "custom-fields")]
+    fn custom(&self) -> Vec<(&str, String)> {
+        custom_fields(self.custom_fields())
+    }
+}
+
+impl Row for Link {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("end1", format!("{{turtle {}}}", self.end1)),
+            ("end2", format!("{{turtle {}}}", self.end2)),
+            ("color", self.color.to_string()),
+            ("label", quote(&self.label)),
+            ("label-color", self.label_color.to_string()),
+            ("hidden?", self.hidden.to_string()),
+            ("directed?", self.is_directed().to_string()),
+            ("breed", link_breed(&self.breed)),
+            ("thickness", self.thickness.to_string()),
+            ("shape", quote(&self.shape)),
+            ("tie-mode", quote(&self.tie_mode)),
+        ]
+    }
+
+    #[cfg(feature = "custom-fields")]
+    fn custom(&self) -> Vec<(&str, String)> {
+        custom_fields(self.custom_fields())
+    }
+}
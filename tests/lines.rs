@@ -0,0 +1,37 @@
+//! The line based core, also available without "std" feature.
+
+use netlogo_world_parser::{parse_lines, parse_lines_with, ParseError, ParseOptions, Section};
+
+#[test]
+fn parse_lines_core() {
+    let world = parse_lines(include_str!("../tests/ants.dat").lines()).expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+    assert_eq!(world.sections_seen().len(), 8);
+    // The random state is a single field longer than the initial buffer
+    let random_state = world.random_state.as_ref().expect("no random state");
+    assert!(random_state.to_string().len() > 1024);
+
+    let bom = parse_lines(include_str!("../tests/ants-bom.dat").lines()).expect("parse failed");
+    assert_eq!(bom.header, world.header);
+    assert_eq!(bom.turtles, world.turtles);
+
+    let options = ParseOptions {
+        delimiter: b';',
+        ..ParseOptions::default()
+    };
+    let data = include_str!("../tests/ants-semicolon.dat");
+    let semicolon = parse_lines_with(data.lines(), options).expect("parse failed");
+    assert_eq!(semicolon.patches, world.patches);
+
+    let options = ParseOptions {
+        comment: Some(b';'),
+        ..ParseOptions::default()
+    };
+    let data = include_str!("../tests/ants-annotated.dat");
+    let annotated = parse_lines_with(data.lines(), options).expect("parse failed");
+    assert_eq!(annotated.turtles, world.turtles);
+    assert_eq!(annotated.sections_seen()[0], Section::RandomState);
+
+    assert!(matches!(parse_lines(vec!["", " "]), Err(ParseError::Empty)));
+}
@@ -1,7 +1,16 @@
-use netlogo_world_parser::parse_str;
+#![cfg(feature = "std")]
+
+use netlogo_world_parser::{
+    for_each_record, for_each_record_with, parse_bytes, parse_file, parse_lines, parse_many,
+    parse_str, parse_turtles_as, parse_with, parse_with_progress, write, Agent, Color, Event,
+    LinkKind, NetLogoWorld, ParseError, ParseOptions, PenMode, Section, SectionMask, Turtle,
+    ValidationError, Warning, WorldReader, WorldSummary,
+};
+#[cfg(feature = "custom-fields")]
 use std::convert::{TryFrom, TryInto};
 
 #[test]
+#[cfg(feature = "custom-fields")]
 fn parse_valid_file() {
     let data = include_str!("../tests/ants.dat");
     let world = parse_str(data).expect("parse failed");
@@ -27,3 +36,2072 @@ fn parse_valid_file() {
 
     assert!(world.output[0].contains("Setup complete"));
 }
+
+#[test]
+fn truncated_quoted_field_is_an_error() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15"#;
+    assert!(parse_str(data).is_err());
+}
+
+#[test]
+fn deserialize_error_reports_section() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"x","15","0","0","0","""default""","""""","9.9","{turtles}","false","1","1","""up"""
+"#;
+    match parse_str(data) {
+        Err(ParseError::Deserialize { section, .. }) => assert_eq!(section, "TURTLES"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn missing_field_is_reported() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    match parse_str(data) {
+        Err(ParseError::MissingField { section, field }) => {
+            assert_eq!((section.as_str(), field.as_str()), ("TURTLES", "ycor"))
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_error_reports_line() {
+    let data =
+        include_str!("../tests/ants.dat").replace(r#""2","15","270""#, r#""two","15","270""#);
+    match parse_str(&data) {
+        Err(ParseError::Deserialize { section, line, .. }) => {
+            assert_eq!(section, "TURTLES");
+            assert_eq!(line, 16);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_patches() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.patches.len(), 25);
+
+    let patch = &world.patches[0];
+    assert_eq!((patch.pxcor, patch.pycor), (-2, 2));
+    assert_eq!(patch.pcolor, Color::Index(115.0));
+    assert_eq!(patch.plabel, None);
+    assert_eq!(patch.plabel_color, 9.9);
+
+    let patch = &world.patches[24];
+    assert_eq!((patch.pxcor, patch.pycor), (2, -2));
+}
+
+#[test]
+fn parse_directed_links() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"LINKS"
+"end1","end2","color","label","label-color","hidden?","directed?","breed","thickness","shape","tie-mode"
+"{turtle 0}","{turtle 1}","5","""""","9.9","false","true","{links}","0","""default""","""none"""
+"{turtle 2}","{turtle 0}","5","""""","9.9","false","false","{links}","0","""default""","""none"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.links.len(), 2);
+
+    let link = &world.links[0];
+    assert_eq!((link.end1, link.end2), (0, 1));
+    assert_eq!(link.kind, LinkKind::Directed);
+    assert!(link.is_directed());
+    assert_eq!(link.shape, "default");
+
+    let link = &world.links[1];
+    assert_eq!((link.end1, link.end2), (2, 0));
+    assert_eq!(link.kind, LinkKind::Undirected);
+    assert!(link.kind.is_undirected() && !link.is_directed());
+
+    let json = serde_json::to_value(&world.links).expect("serialize failed");
+    assert_eq!(json[0]["directed?"], true);
+    assert_eq!(json[1]["directed?"], false);
+}
+
+#[test]
+fn parse_link_breeds() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"LINKS"
+"end1","end2","color","label","label-color","hidden?","directed?","breed","thickness","shape","tie-mode"
+"{turtle 0}","{turtle 1}","5","""""","9.9","false","true","{breed roads}","0","""default""","""none"""
+"{turtle 2}","{turtle 0}","5","""""","9.9","false","false","{breed friendships}","0","""default""","""none"""
+"{turtle 1}","{turtle 2}","5","""""","9.9","false","true","{breed roads}","0","""default""","""none"""
+"{turtle 1}","{turtle 0}","5","""""","9.9","false","false","{links}","0","""default""","""none"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.links_of_breed("roads").count(), 2);
+    assert_eq!(world.links_of_breed("Friendships").count(), 1);
+    assert_eq!(world.links[3].breed, "links");
+    assert!(world.links_of_breed("roads").all(|link| link.is_directed()));
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reparsed = parse_bytes(&written).expect("reparse failed");
+    assert_eq!(reparsed.links, world.links);
+}
+
+#[test]
+fn parse_capitalized_flags() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"LINKS"
+"end1","end2","color","label","label-color","hidden?","directed?","breed","thickness","shape","tie-mode"
+"{turtle 0}","{turtle 1}","5","""""","9.9","False","TRUE","{links}","0","""default""","""none"""
+"{turtle 2}","{turtle 0}","5","""""","9.9","True","false","{links}","0","""default""","""none"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert!(!world.links[0].hidden && world.links[0].is_directed());
+    assert!(world.links[1].hidden && !world.links[1].is_directed());
+}
+
+#[test]
+fn parse_empty_links() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"LINKS"
+"end1","end2","color","label","label-color","hidden?","breed","thickness","shape","tie-mode"
+
+"OUTPUT"
+"""first\nsecond"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert!(world.links.is_empty());
+    assert_eq!(world.output, vec!["first", "second"]);
+    assert_eq!(world.link_fields().len(), 10);
+
+    // No header row at all
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"LINKS"
+"OUTPUT"
+"""first"""
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"0","0","0","""""","9.9"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert!(world.links.is_empty());
+    assert!(world.link_fields().is_empty());
+    assert_eq!(world.output, vec!["first"]);
+    assert_eq!(world.patches.len(), 1);
+}
+
+#[test]
+fn parse_plots() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"PLOTS"
+"Population"
+"""Population"""
+"x min","x max","y min","y max","autoplot?","current pen","legend open?","number of pens"
+"0","10","0","10","true","""turtles""","false","1"
+
+"pen name","pen down?","mode","interval","color","x"
+"""turtles""","true","0","1","0","3"
+
+"""turtles"""
+"x","y","color","pen down?"
+"0","6","0","true"
+"1","5","0","true"
+"2","4.5","0","true"
+
+"""Food"""
+"x min","x max","y min","y max","autoplot?","current pen","legend open?","number of pens"
+"0","50","0","120","true","""pile1""","true","2"
+
+"pen name","pen down?","mode","interval","color","x"
+"""pile1""","true","0","1","85","2"
+"""pile2""","false","0","1","95","1"
+
+"""pile1""",,,,"""pile2""",,,
+"x","y","color","pen down?","x","y","color","pen down?"
+"0","100","85","true","0","80","95","true"
+"1","90","85","true",,,,
+
+"EXTENSIONS"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.current_plot, "Population");
+    assert_eq!(world.plots.len(), 2);
+
+    let plot = &world.plots[0];
+    assert_eq!(plot.name, "Population");
+    assert_eq!(plot.current_pen, "turtles");
+    let pen = plot.pen("turtles").expect("no pen");
+    let ys: Vec<f64> = pen.points.iter().map(|point| point.y).collect();
+    assert_eq!(ys, vec![6.0, 5.0, 4.5]);
+
+    let plot = &world.plots[1];
+    assert_eq!(plot.name, "Food");
+    assert_eq!((plot.x_max, plot.y_max), (50.0, 120.0));
+    assert_eq!(plot.pens.len(), 2);
+    assert_eq!(plot.pens[0].points.len(), 2);
+    assert_eq!(plot.pens[1].points.len(), 1);
+    assert!(!plot.pens[1].pen_down);
+}
+
+#[test]
+fn parse_ants_plot() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.plots.len(), 1);
+    let plot = &world.plots[0];
+    assert_eq!(plot.name, "Food in each pile");
+    let names: Vec<&str> = plot.pens.iter().map(|pen| pen.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["food-in-pile1", "food-in-pile2", "food-in-pile3"]
+    );
+    assert!(plot.pens.iter().all(|pen| pen.points.len() == 1));
+}
+
+#[test]
+fn parse_extensions() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"EXTENSIONS"
+
+"TABLE"
+"{{table: 0: [[""apples"" 3] [""pears"" 5]]}}"
+"{{table: 1: []}}"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.extensions.len(), 1);
+    let table = &world.extensions[0];
+    assert_eq!(table.name, "TABLE");
+    assert_eq!(table.rows.len(), 2);
+    assert_eq!(
+        table.rows[0],
+        vec![r#"{{table: 0: [["apples" 3] ["pears" 5]]}}"#]
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn turtle_type_names() {
+    use netlogo_world_parser::Turle;
+
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let turtle: &Turtle = &world.turtles[0];
+    let turle: &Turle = turtle;
+    assert_eq!(format!("{:?}", turtle), format!("{:?}", turle));
+}
+
+#[test]
+fn parse_from_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/ants.dat");
+    let world = parse_file(path).expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+}
+
+#[test]
+fn parse_semicolon_delimited() {
+    let data = include_str!("../tests/ants-semicolon.dat");
+    let options = ParseOptions {
+        delimiter: b';',
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+    assert_eq!(world.globals.max_pxcor, 2);
+    assert_eq!(world.plots[0].pens.len(), 3);
+}
+
+#[test]
+fn parse_annotated() {
+    let data = include_str!("../tests/ants-annotated.dat");
+    assert!(parse_str(data).is_err());
+
+    let options = ParseOptions {
+        comment: Some(b';'),
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    let expected = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    assert_same_world(&world, &expected);
+}
+
+#[test]
+fn parse_turtles_as_own_type() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Position {
+        who: usize,
+        xcor: i64,
+        ycor: i64,
+    }
+
+    let data = include_str!("../tests/ants.dat");
+    let turtles: Vec<Position> = parse_turtles_as(data.as_bytes()).expect("parse failed");
+    assert_eq!(turtles.len(), 6);
+    assert_eq!(
+        turtles[5],
+        Position {
+            who: 5,
+            xcor: 0,
+            ycor: 0
+        }
+    );
+}
+
+#[test]
+fn parse_turtles_as_skips_unknown_sections_and_breed_headers() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Energy {
+        who: usize,
+        #[serde(default)]
+        energy: Option<u64>,
+    }
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","wool"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","true"
+
+"FOOBAR"
+"foo","bar"
+"1","2"
+
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"0","0","0","""""","9.9"
+"#;
+    let turtles: Vec<Energy> = parse_turtles_as(data.as_bytes()).expect("parse failed");
+    assert_eq!(
+        turtles,
+        [
+            Energy {
+                who: 0,
+                energy: Some(12)
+            },
+            Energy {
+                who: 1,
+                energy: None
+            }
+        ]
+    );
+}
+
+#[test]
+fn parse_fractional_ticks() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks"
+"-1","1","-1","1","12.5"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.ticks, 12.5);
+}
+
+#[test]
+fn parse_legacy_clock() {
+    for heading in &["CLOCK", "TICKS"] {
+        let data = format!(
+            r#""export-world data (NetLogo 4.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor"
+"-1","1","-1","1"
+
+"{}"
+"ticks"
+"42"
+"#,
+            heading
+        );
+        let world = parse_str(&data).expect("parse failed");
+        assert_eq!(world.globals.ticks, 42.0);
+        assert_eq!(world.globals.max_pxcor, 1);
+        assert_eq!(world.sections_seen(), &[Section::Globals, Section::Clock]);
+    }
+}
+
+#[test]
+fn world_bounds() {
+    let data = include_str!("../tests/ants.dat");
+    let globals = parse_str(data).expect("parse failed").globals;
+    assert_eq!((globals.width(), globals.height()), (5, 5));
+    assert_eq!(globals.wrap(0, 0), (0, 0));
+    assert_eq!(globals.wrap(2, 2), (2, 2));
+    assert_eq!(globals.wrap(3, 0), (-2, 0));
+    assert_eq!(globals.wrap(8, 0), (-2, 0));
+    assert_eq!(globals.wrap(-3, -7), (2, -2));
+
+    // No panics on degenerate bounds
+    let data = r#""GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor"
+"0","-1","0","1"
+"#;
+    let globals = parse_str(data).expect("parse failed").globals;
+    assert_eq!((globals.width(), globals.height()), (0, 2));
+    assert_eq!(globals.wrap(5, 3), (5, 1));
+}
+
+#[test]
+fn parse_sections_seen() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(
+        world.sections_seen(),
+        &[
+            Section::RandomState,
+            Section::Globals,
+            Section::Turtles,
+            Section::Patches,
+            Section::Links,
+            Section::Output,
+            Section::Plots,
+            Section::Extensions,
+        ]
+    );
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.sections_seen(), &[Section::Turtles]);
+}
+
+#[test]
+fn sections_as_keys() {
+    use std::collections::HashSet;
+
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let seen: HashSet<Section> = world.sections_seen().iter().copied().collect();
+    assert!(seen.contains(&Section::Links));
+    assert!(!seen.contains(&Section::Header));
+    assert_ne!(Section::Turtles, Section::Patches);
+    assert_eq!(Section::RandomState.name(), "RANDOM STATE");
+}
+
+#[test]
+fn parse_from_bytes() {
+    let data: Vec<u8> = include_bytes!("../tests/ants.dat").to_vec();
+    let world = parse_bytes(&data).expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+}
+
+#[test]
+fn parse_with_bom() {
+    let plain = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let data = include_bytes!("../tests/ants-bom.dat");
+    assert!(data.starts_with(b"\xef\xbb\xbf"));
+    let world = parse_bytes(data).expect("parse failed");
+    assert_same_world(&world, &plain);
+    assert_eq!(world.header, plain.header);
+
+    let data = "\u{feff}\"GLOBALS\"
+\"min-pxcor\",\"max-pxcor\",\"min-pycor\",\"max-pycor\",\"ticks\"
+\"-3\",\"3\",\"-1\",\"1\",\"7\"
+";
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.sections_seen(), &[Section::Globals]);
+    assert_eq!((world.globals.max_pxcor, world.globals.ticks), (3, 7.0));
+}
+
+#[test]
+fn validate_world() {
+    let data = include_str!("../tests/ants.dat");
+    assert_eq!(parse_str(data).expect("parse failed").validate(), Ok(()));
+
+    let last_patch = r#""2","-2","115","""""","9.9","0","2","true","197.17157287525382","2""#;
+    let truncated = data.replace(last_patch, "");
+    let world = parse_str(&truncated).expect("parse failed");
+    assert_eq!(
+        world.validate(),
+        Err(ValidationError::PatchCount {
+            expected: 25,
+            found: 24
+        })
+    );
+
+    let duplicated = data.replace(r#""5","15","16""#, r#""4","15","16""#);
+    let world = parse_str(&duplicated).expect("parse failed");
+    assert_eq!(world.validate(), Err(ValidationError::DuplicateWho(4)));
+}
+
+#[test]
+fn turtles_of_breed() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""ant""","""""","9.9","{breed ants}","false","1","1","""up"""
+"1","15","0","0","0","""ant""","""""","9.9","{breed ants}","false","1","1","""up"""
+"2","45","0","0","0","""queen""","""""","9.9","{breed queens}","false","2","1","""up"""
+"3","5","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtles_of_breed("ants").count(), 2);
+    assert_eq!(world.turtles_of_breed("ANTS").count(), 2);
+    assert_eq!(world.turtles_of_breed("queens").count(), 1);
+    assert_eq!(world.turtles_of_breed("turtles").count(), 1);
+    assert_eq!(world.turtles_of_breed("wolves").count(), 0);
+    assert_eq!(world.turtles[2].breed, "queens");
+}
+
+#[test]
+fn serialize_to_json() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let json = serde_json::to_value(&world).expect("serialize failed");
+
+    assert_eq!(json["globals"]["max-pxcor"], 2);
+    assert_eq!(json["turtles"][1]["heading"], 266.0);
+    assert_eq!(json["turtles"][1]["shape"], "bug");
+    assert_eq!(json["turtles"][1]["hidden?"], false);
+    assert_eq!(json["patches"].as_array().map(Vec::len), Some(25));
+    assert_eq!(json["plots"][0]["name"], "Food in each pile");
+    assert_eq!(json["plots"][0]["pens"][1]["pen name"], "food-in-pile2");
+
+    #[cfg(feature = "custom-fields")]
+    {
+        assert_eq!(json["globals"]["population"], 6);
+        assert_eq!(json["globals"]["subject"], serde_json::Value::Null);
+        assert_eq!(json["patches"][12]["nest?"], true);
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let data = include_str!("../tests/ants.dat")
+        .replace(r#""0","15","214""#, r#""0","[255 0 128]","214""#)
+        .replacen(r#""""""","9.9""#, r#""""ant""","9.9""#, 1);
+    let world = parse_str(&data).expect("parse failed");
+    assert_eq!(world.turtles[0].color, Color::Rgb(255, 0, 128));
+    assert_eq!(world.turtles[0].label.as_deref(), Some("ant"));
+    assert_eq!(world.turtles[1].label, None);
+
+    let json = serde_json::to_string(&world).expect("serialize failed");
+    let back: NetLogoWorld = serde_json::from_str(&json).expect("deserialize failed");
+    assert_eq!(world, back);
+}
+
+#[test]
+fn keep_unknown_sections() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks"
+"-1","1","-1","1","0"
+
+"FOOBAR"
+"foo","bar"
+"1","""baz"""
+
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"0","0","0","""""","9.9"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.max_pxcor, 1);
+    assert_eq!(world.patches.len(), 1);
+    assert_eq!(
+        world.unknown_section("FOOBAR").expect("no section"),
+        [vec!["foo", "bar"], vec!["1", "\"baz\""]]
+    );
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reparsed = parse_bytes(&written).expect("reparse failed");
+    assert_eq!(reparsed.unknown_sections, world.unknown_sections);
+}
+
+#[test]
+fn keep_unknown_sections_order() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"ZULU"
+"z"
+
+"ALPHA"
+"a"
+
+"ZULU"
+"zz"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let names: Vec<&str> = world
+        .unknown_sections
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names, ["ZULU", "ALPHA"]);
+    assert_eq!(
+        world.unknown_section("ZULU").expect("no section"),
+        [vec!["z"], vec!["zz"]]
+    );
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let written = String::from_utf8(written).expect("not utf-8");
+    let zulu = written.find("\"ZULU\"").expect("no ZULU");
+    let alpha = written.find("\"ALPHA\"").expect("no ALPHA");
+    assert!(zulu < alpha);
+    let reparsed = parse_str(&written).expect("reparse failed");
+    assert_eq!(reparsed.unknown_sections, world.unknown_sections);
+}
+
+#[test]
+fn strict_sections() {
+    let data = include_str!("../tests/ants.dat");
+    let options = ParseOptions {
+        strict_sections: true,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with(data.as_bytes(), options.clone()).is_ok());
+
+    let data = data.replace("\"PATCHES\"", "\"FOOBAR\"\n\"foo\"\n\n\"PATCHES\"");
+    assert!(parse_str(&data).is_ok());
+    match parse_with(data.as_bytes(), options) {
+        Err(ParseError::UnexpectedSection(name)) => assert_eq!(name, "FOOBAR"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn warn_about_unknown_sections() {
+    let data = include_str!("../tests/ants.dat");
+    assert_eq!(parse_str(data).expect("parse failed").warnings(), &[]);
+
+    let data = data.replace("\"PATCHES\"", "\"FOOBAR\"\n\"foo\"\n\n\"PATCHES\"");
+    let world = parse_str(&data).expect("parse failed");
+    assert_eq!(
+        world.warnings(),
+        &[Warning::UnknownSection("FOOBAR".to_string())]
+    );
+    assert_eq!(
+        world.warnings()[0].to_string(),
+        "Unknown section FOOBAR kept as raw rows"
+    );
+}
+
+#[test]
+fn parse_rgb_colors() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"0","0","[255 0 128]","""""","9.9"
+"1","0","15","""""","9.9"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.patches[0].pcolor, Color::Rgb(255, 0, 128));
+    assert_eq!(world.patches[1].pcolor, Color::Index(15.0));
+    assert_eq!(world.patches[1].pcolor.to_rgb(), (215, 50, 41));
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reparsed = parse_bytes(&written).expect("reparse failed");
+    assert_eq!(reparsed.patches[0].pcolor, Color::Rgb(255, 0, 128));
+}
+
+#[test]
+fn parse_selected_sections() {
+    let data = include_str!("../tests/ants.dat");
+    let options = ParseOptions {
+        sections: SectionMask::none()
+            .with(Section::Globals)
+            .with(Section::Turtles),
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert_eq!(world.globals.max_pxcor, 2);
+    assert_eq!(world.turtles.len(), 6);
+    assert!(world.header.is_none());
+    assert!(world.random_state.is_none());
+    assert!(world.patches.is_empty());
+    assert!(world.plots.is_empty());
+    assert!(world.output.is_empty());
+
+    let options = ParseOptions {
+        sections: SectionMask::all().without(Section::Patches),
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert!(world.patches.is_empty());
+    assert_eq!(world.plots.len(), 1);
+}
+
+#[test]
+fn section_fields() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(
+        world.turtle_fields(),
+        &[
+            "who",
+            "color",
+            "heading",
+            "xcor",
+            "ycor",
+            "shape",
+            "label",
+            "label-color",
+            "breed",
+            "hidden?",
+            "size",
+            "pen-size",
+            "pen-mode"
+        ]
+    );
+    assert_eq!(world.patch_fields().len(), 10);
+    assert_eq!(world.patch_fields()[5], "chemical");
+    assert_eq!(world.link_fields()[0], "end1");
+    assert_eq!(
+        world.global_fields().last().map(String::as_str),
+        Some("population")
+    );
+    assert!(world.fields(Section::Output).is_empty());
+}
+
+#[test]
+fn iterate_agents() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.agents().count(), 31);
+    let turtles = world
+        .agents()
+        .filter(|agent| matches!(agent, Agent::Turtle(_)))
+        .count();
+    assert_eq!(turtles, 6);
+    let agent = world.agents().nth(6);
+    match agent {
+        Some(Agent::Patch(patch)) => assert_eq!((patch.pxcor, patch.pycor), (-2, 2)),
+        other => panic!("unexpected agent: {:?}", other),
+    }
+}
+
+#[test]
+fn skip_whitespace_records() {
+    let data = include_str!("../tests/ants.dat");
+    let clean = parse_str(data).expect("parse failed");
+    let messy = data
+        .replace("\n\"TURTLES\"", "\n   \n\"TURTLES\"")
+        .replace("\n\"PATCHES\"", "\n\t,  \n\"PATCHES\"")
+        + "\n\n  \n\n";
+    let world = parse_str(&messy).expect("parse failed");
+    assert_same_world(&world, &clean);
+}
+
+#[test]
+fn patch_grid() {
+    let data = include_str!("../tests/ants.dat");
+    let mut world = parse_str(data).expect("parse failed");
+    let grid = world.patch_grid();
+    for &(x, y) in &[(-2, 2), (2, 2), (-2, -2), (2, -2), (1, 0)] {
+        let patch = grid.get(x, y).expect("no patch");
+        assert_eq!((patch.pxcor, patch.pycor), (x, y));
+    }
+    assert_eq!(grid.get(2, -2).map(|patch| patch.plabel_color), Some(9.9));
+    assert!(grid.get(3, 0).is_none());
+    assert!(grid.get(0, -3).is_none());
+
+    world.patches.reverse();
+    let patch = world.patch_grid().get(-2, 2).expect("no patch");
+    assert_eq!((patch.pxcor, patch.pycor), (-2, 2));
+}
+
+#[test]
+fn report_progress() {
+    let data = include_str!("../tests/ants.dat");
+    let patch = r#""0","0","115","""""","9.9","0","2","true","200","3""#;
+    let big = data.replacen(patch, &format!("{}\n", patch).repeat(5000), 1);
+
+    let mut offsets = vec![];
+    let world = parse_with_progress(big.as_bytes(), ParseOptions::default(), |bytes| {
+        offsets.push(bytes)
+    })
+    .expect("parse failed");
+    assert_eq!(world.patches.len(), 25 + 4999);
+    assert!(offsets.len() > 2);
+    assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(offsets.last().copied(), Some(big.len() as u64));
+}
+
+#[test]
+fn parallel_patches_match_sequential() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let sequential: Vec<_> = WorldReader::new(data.as_bytes())
+        .events()
+        .filter_map(|event| match event.expect("parse failed") {
+            Event::Patch(patch) => Some(patch),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(format!("{:?}", world.patches), format!("{:?}", sequential));
+}
+
+#[test]
+fn count_turtles_by_color() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let counts = world.turtles_by_color();
+    assert_eq!(counts.values().sum::<usize>(), world.turtles.len());
+    assert_eq!(counts.get(&15), Some(&6));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn parse_from_async_reader() {
+    let data = include_str!("../tests/ants.dat");
+    let world = netlogo_world_parser::parse_async(data.as_bytes())
+        .await
+        .expect("parse failed");
+    assert_same_world(&world, &parse_str(data).expect("parse failed"));
+    assert_eq!(world.sections_seen().len(), 8);
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""default""","""two
+lines""","9.9","{all-turtles}","false","1","1","""up"""
+"x","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    match netlogo_world_parser::parse_async(data.as_bytes()).await {
+        Err(ParseError::Deserialize { line, .. }) => assert_eq!(line, 6),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn parse_from_async_reader_with_options() {
+    let data = include_str!("../tests/ants-semicolon.dat");
+    let options = ParseOptions {
+        delimiter: b';',
+        sections: SectionMask::none()
+            .with(Section::Globals)
+            .with(Section::Turtles),
+        ..ParseOptions::default()
+    };
+    let world = netlogo_world_parser::parse_async_with(data.as_bytes(), options)
+        .await
+        .expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert!(world.patches.is_empty());
+
+    let data = include_str!("../tests/ants.dat")
+        .replace(r#""1","15","266","0","0""#, r#""0","15","266","0","0""#);
+    let options = ParseOptions {
+        validate: true,
+        ..ParseOptions::default()
+    };
+    match netlogo_world_parser::parse_async_with(data.as_bytes(), options).await {
+        Err(ParseError::Invalid(ValidationError::DuplicateWho(0))) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_turtles_only() {
+    let data = r#""TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","90","1","-1","""bug""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"1","25","180","0","0","""bug""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert!(world.header.is_none());
+    assert_eq!(world.sections_seen(), &[Section::Turtles]);
+    assert_eq!(world.turtles.len(), 2);
+    assert_eq!((world.turtles[0].xcor, world.turtles[0].ycor), (1, -1));
+    // No bounds to count patches by
+    assert_eq!(world.turtle_density(), None);
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reparsed = parse_bytes(&written).expect("reparse failed");
+    assert!(!reparsed.sections_seen().contains(&Section::Globals));
+    assert_eq!(reparsed.turtle_density(), None);
+    assert_eq!(world.turtles, reparsed.turtles);
+}
+
+#[test]
+fn turtle_pen() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","3","""down"""
+"1","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""erase"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let turtle = &world.turtles[0];
+    assert_eq!((turtle.pen_mode.as_str(), turtle.pen_size), ("down", 3.0));
+    assert!(turtle.is_pen_down());
+    assert!(!world.turtles[1].is_pen_down());
+    assert!(!parse_str(include_str!("../tests/ants.dat"))
+        .expect("parse failed")
+        .turtles[0]
+        .is_pen_down());
+}
+
+#[test]
+fn parse_fractional_turtle_color() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","9.9","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"1","45.0","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtles[0].color, Color::Index(9.9));
+    assert_eq!(world.turtles[1].color, Color::Index(45.0));
+}
+
+#[test]
+fn parse_from_lines() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_lines(data.lines()).expect("parse failed");
+    assert_same_world(&world, &parse_str(data).expect("parse failed"));
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""default""","""two
+lines""","9.9","{all-turtles}","false","1","1","""up"""
+"x","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    let lines: Vec<&str> = data.lines().take(5).collect();
+    let world = parse_lines(lines).expect("parse failed");
+    assert_eq!(world.turtles[0].label.as_deref(), Some("two\nlines"));
+    match parse_lines(data.lines()) {
+        Err(ParseError::Deserialize { line, .. }) => assert_eq!(line, 6),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_validated() {
+    let data = include_str!("../tests/ants.dat");
+    let options = ParseOptions {
+        validate: true,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with(data.as_bytes(), options.clone()).is_ok());
+
+    let duplicated = data.replace(r#""1","15","266","0","0""#, r#""0","15","266","0","0""#);
+    assert!(parse_str(&duplicated).is_ok());
+    match parse_with(duplicated.as_bytes(), options) {
+        Err(ParseError::Invalid(ValidationError::DuplicateWho(0))) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn parse_wrapping_flags() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.wrapping_allowed_in_x, None);
+    assert_eq!(world.globals.wrapping_allowed_in_y, None);
+
+    let data = data
+        .replace(
+            r#""evaporation-rate","population""#,
+            r#""evaporation-rate","population","wrapping-allowed-in-x","wrapping-allowed-in-y""#,
+        )
+        .replace(
+            r#""0","50","10","6""#,
+            r#""0","50","10","6","true","FALSE""#,
+        );
+    let world = parse_str(&data).expect("parse failed");
+    assert_eq!(world.globals.wrapping_allowed_in_x, Some(true));
+    assert_eq!(world.globals.wrapping_allowed_in_y, Some(false));
+    assert_eq!((world.globals.width(), world.globals.height()), (5, 5));
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reread = parse_bytes(&written).expect("reparse failed");
+    assert_eq!(reread.globals.wrapping_allowed_in_y, Some(false));
+}
+
+#[test]
+fn parse_breed_headers() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","wool","age"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","true","3"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtles.len(), 2);
+    assert_eq!(world.turtles[1].breed, "sheep");
+    let fields = world.turtle_fields();
+    assert_eq!(fields[fields.len() - 3..], ["energy", "wool", "age"]);
+    #[cfg(feature = "custom-fields")]
+    {
+        use netlogo_world_parser::Value;
+        assert_eq!(world.turtles[0].get("energy"), Some(&Value::U64(12)));
+        assert_eq!(world.turtles[1].get("energy"), None);
+        assert_eq!(world.turtles[1].get("wool"), Some(&Value::Bool(true)));
+        assert_eq!(world.turtles[1].get("age"), Some(&Value::U64(3)));
+    }
+}
+
+#[test]
+fn parse_empty_labels() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"1","15","0","0","0","""default""","""queen""","9.9","{all-turtles}","false","1","1","""up"""
+
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"0","0","0","""""","9.9"
+"1","0","0","""nest""","9.9"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtles[0].label, None);
+    assert_eq!(world.turtles[1].label.as_deref(), Some("queen"));
+    assert_eq!(world.patches[0].plabel, None);
+    assert_eq!(world.patches[1].plabel.as_deref(), Some("nest"));
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let written = String::from_utf8(written).expect("not utf-8");
+    assert!(written.contains(r#""0","0","0","""""","9.9""#));
+    assert!(written.contains(r#""1","0","0","""nest""","9.9""#));
+}
+
+#[test]
+fn merge_worlds() {
+    let data = include_str!("../tests/ants.dat");
+    let parse_only = |sections: SectionMask| {
+        let options = ParseOptions {
+            sections,
+            ..ParseOptions::default()
+        };
+        parse_with(data.as_bytes(), options).expect("parse failed")
+    };
+    let turtles = || parse_only(SectionMask::none().with(Section::Turtles));
+    let patches = || {
+        parse_only(
+            SectionMask::none()
+                .with(Section::Globals)
+                .with(Section::Patches),
+        )
+    };
+
+    let mut world = turtles();
+    world.merge(patches());
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+    assert_eq!(world.globals.max_pxcor, 2);
+    assert!(!world.patch_fields().is_empty());
+    assert_eq!(world.validate(), Ok(()));
+
+    // Globals of a world without them don't override ours
+    let mut world = patches();
+    world.merge(turtles());
+    assert_eq!(world.globals.max_pxcor, 2);
+    assert_eq!(world.turtles.len(), 6);
+}
+
+#[test]
+fn summarize_world() {
+    let world = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let summary = world.summary();
+    assert_eq!(
+        summary,
+        WorldSummary {
+            turtles: 6,
+            patches: 25,
+            links: 0,
+            ticks: 0.0,
+            width: 5,
+            height: 5,
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "5x5 world at tick 0 with 6 turtles, 25 patches and 0 links"
+    );
+}
+
+#[test]
+fn parse_crlf() {
+    let data = include_str!("../tests/ants.dat");
+    let expected = parse_str(data).expect("parse failed");
+    let crlf = data.replace('\n', "\r\n");
+    let world = parse_str(&crlf).expect("parse failed");
+    assert_same_world(&world, &expected);
+    assert_eq!(world.sections_seen(), expected.sections_seen());
+    assert_same_world(
+        &parse_lines(crlf.split('\n')).expect("parse failed"),
+        &expected,
+    );
+
+    // Unquoted headings keep a carriage return if only `\n` ends lines
+    let mixed = data.replace("\"TURTLES\"\n", "TURTLES\r\r\n");
+    assert_same_world(&parse_str(&mixed).expect("parse failed"), &expected);
+}
+
+#[test]
+fn parse_concatenated_worlds() {
+    let data = include_str!("../tests/ants.dat");
+    let expected = parse_str(data).expect("parse failed");
+    let later = data.replace("15:20:03:864", "15:25:00:000");
+    let batch = format!("{}{}", data, later);
+    let worlds = parse_many(batch.as_bytes()).expect("parse failed");
+    assert_eq!(worlds.len(), 2);
+    assert_same_world(&worlds[0], &expected);
+    assert_eq!(worlds[0].sections_seen(), expected.sections_seen());
+    assert_eq!(worlds[1].turtles.len(), 6);
+    assert_eq!(worlds[1].sections_seen(), expected.sections_seen());
+    let header = worlds[1].header.as_ref().expect("no header");
+    assert_eq!(header.exported_at, "08/07/2019 15:25:00:000 +0200");
+
+    let single = parse_many(data.as_bytes()).expect("parse failed");
+    assert_eq!(single.len(), 1);
+    assert_same_world(&single[0], &expected);
+}
+
+#[test]
+fn compare_worlds() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world, parse_str(data).expect("parse failed"));
+
+    let mut moved = parse_str(data).expect("parse failed");
+    moved.turtles[0].xcor = 1;
+    assert_ne!(world, moved);
+
+    // What the parser learned about the file isn't compared
+    let options = ParseOptions {
+        keep_float_text: true,
+        ..ParseOptions::default()
+    };
+    let texts = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert_eq!(world, texts);
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    assert_eq!(world, parse_bytes(&written).expect("reparse failed"));
+}
+
+#[test]
+fn count_records() {
+    let world = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let counts = world.record_counts();
+    assert_eq!(counts[&Section::Turtles], world.turtles.len());
+    assert_eq!(counts[&Section::Patches], world.patches.len());
+    assert_eq!(counts[&Section::Globals], 1);
+    assert_eq!(counts.get(&Section::Links), None);
+}
+
+#[test]
+fn parse_who_edge_cases() {
+    let turtles = |who: &str| {
+        format!(
+            r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""default""","""""","9.9","{{all-turtles}}","false","1","1","""up"""
+"{}","15","0","0","0","""default""","""""","9.9","{{all-turtles}}","false","1","1","""up"""
+"#,
+            who
+        )
+    };
+    let world = parse_str(&turtles("4294967295")).expect("parse failed");
+    assert_eq!(world.turtles[0].who, 0);
+    assert_eq!(world.turtles[1].who, 4_294_967_295);
+    assert!(world.turtle(4_294_967_295).is_some());
+
+    match parse_str(&turtles("-1")) {
+        Err(err @ ParseError::Deserialize { line: 5, .. }) => {
+            assert!(
+                err.to_string().contains("negative who number: -1"),
+                "{}",
+                err
+            )
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn patches_in_region() {
+    let world = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let region: Vec<(i64, i64)> = world
+        .patches_in(0..=2, -1..=1)
+        .map(|patch| (patch.pxcor, patch.pycor))
+        .collect();
+    assert_eq!(region.len(), 9);
+    assert_eq!(region[0], (0, 1));
+    assert_eq!(region[8], (2, -1));
+
+    // Clamped to the world bounds
+    assert_eq!(world.patches_in(1..=10, -10..=10).count(), 10);
+    assert_eq!(world.patches_in(3..=4, 0..=0).count(), 0);
+}
+
+#[test]
+fn list_breeds() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up"""
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up"""
+"2","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up"""
+"3","15","0","0","0","""default""","""""","9.9","{all-turtles}","false","1","1","""up"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.breeds(), vec!["wolves", "sheep", "turtles"]);
+    assert!(NetLogoWorld::default().breeds().is_empty());
+}
+
+#[test]
+fn patches_in_export_order() {
+    let mut world = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let coords = |patches: &[&netlogo_world_parser::Patch]| -> Vec<(i64, i64)> {
+        patches
+            .iter()
+            .map(|patch| (patch.pxcor, patch.pycor))
+            .collect()
+    };
+    let exported = coords(&world.patches.iter().collect::<Vec<_>>());
+    assert_eq!(
+        exported[..6],
+        [(-2, 2), (-1, 2), (0, 2), (1, 2), (2, 2), (-2, 1)]
+    );
+    assert_eq!(coords(&world.patches_in_export_order()), exported);
+
+    world.patches.reverse();
+    assert_eq!(coords(&world.patches_in_export_order()), exported);
+}
+
+#[test]
+fn keep_float_text() {
+    let data = include_str!("../tests/ants.dat");
+    let data = data.replace(r#""0","15","214","0","0""#, r#""0","15","1.5E-7","0","0""#);
+    let written = |world: &NetLogoWorld| {
+        let mut written = vec![];
+        write(world, &mut written).expect("write failed");
+        String::from_utf8(written).expect("not utf-8")
+    };
+
+    let world = parse_str(&data).expect("parse failed");
+    assert_eq!(world.turtles[0].heading, 1.5e-7);
+    assert!(written(&world).contains(r#""0","15","0.00000015","0","0""#));
+
+    let options = ParseOptions {
+        keep_float_text: true,
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options.clone()).expect("parse failed");
+    assert_eq!(world.turtles[0].heading, 1.5e-7);
+    assert!(written(&world).contains(r#""0","15","1.5E-7","0","0""#));
+    assert_eq!(world, parse_str(&written(&world)).expect("reparse failed"));
+
+    // Each field keeps its own spelling of the same value
+    let data = data.replace(r#""1","15","266","0","0""#, r#""1","15","15e-8","0","0""#);
+    let mut world = parse_with(data.as_bytes(), options.clone()).expect("parse failed");
+    assert_eq!(world.turtles[1].heading, world.turtles[0].heading);
+    assert!(written(&world).contains(r#""0","15","1.5E-7","0","0""#));
+    assert!(written(&world).contains(r#""1","15","15e-8","0","0""#));
+    assert!(written(&world).contains(r#""2","15","270","0","0""#));
+
+    let other = parse_with(data.as_bytes(), options).expect("parse failed");
+    world.merge(other);
+    assert_eq!(
+        written(&world)
+            .matches(r#""1","15","15e-8","0","0""#)
+            .count(),
+        2
+    );
+
+    world.turtles[0].heading = 2.5e-7;
+    assert!(written(&world).contains(r#""0","15","0.00000025","0","0""#));
+
+    // Repeated columns keep their texts too
+    #[cfg(feature = "custom-fields")]
+    {
+        let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks","rate","ticks","rate"
+"-1","1","-1","1","1.5E-7","1E-3","2.5E-7","2E-3"
+"#;
+        let options = ParseOptions {
+            keep_float_text: true,
+            ..ParseOptions::default()
+        };
+        let world = parse_with(data.as_bytes(), options).expect("parse failed");
+        assert!(written(&world).contains(r#""-1","1","-1","1","1.5E-7","2E-3","2.5E-7""#));
+    }
+}
+
+#[test]
+fn global_shadowing_builtin() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks","min-pxcor","ticks"
+"-1","1","-1","1","3","-5","7"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.min_pxcor, -1);
+    assert_eq!(world.globals.ticks, 3.0);
+    #[cfg(feature = "custom-fields")]
+    {
+        use netlogo_world_parser::Value;
+        let globals = &world.globals;
+        assert_eq!(globals.get("min-pxcor"), Some(&Value::I64(-5)));
+        assert_eq!(globals.get("ticks"), Some(&Value::U64(7)));
+
+        let mut out = vec![];
+        write(&world, &mut out).expect("write failed");
+        let reparsed = parse_bytes(&out).expect("reparse failed");
+        assert_eq!(reparsed.globals, world.globals);
+    }
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn has_custom_fields() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert!(world.globals.has("population"));
+    assert!(!world.globals.has("ticks"));
+    assert!(world.patches[0].has("chemical"));
+    assert!(!world.patches[0].has("energy"));
+    assert!(!world.turtles[0].has("who"));
+}
+
+#[test]
+fn patch_neighbors() {
+    let data = include_str!("../tests/ants.dat");
+    let mut world = parse_str(data).expect("parse failed");
+    let coords = |patches: Vec<&netlogo_world_parser::Patch>| {
+        let mut coords: Vec<(i64, i64)> = patches.iter().map(|p| (p.pxcor, p.pycor)).collect();
+        coords.sort();
+        coords
+    };
+
+    // The ants world spans from -2 to 2 and wraps
+    let grid = world.patch_grid();
+    assert_eq!(
+        coords(grid.neighbors(2, 2)),
+        [
+            (-2, -2),
+            (-2, 1),
+            (-2, 2),
+            (1, -2),
+            (1, 1),
+            (1, 2),
+            (2, -2),
+            (2, 1)
+        ]
+    );
+    assert_eq!(
+        coords(grid.neighbors4(-2, -2)),
+        [(-2, -1), (-2, 2), (-1, -2), (2, -2)]
+    );
+    assert!(grid.neighbors(3, 3).is_empty());
+
+    world.globals.wrapping_allowed_in_x = Some(false);
+    world.globals.wrapping_allowed_in_y = Some(false);
+    assert_eq!(
+        coords(world.patch_grid().neighbors(2, 2)),
+        [(1, 1), (1, 2), (2, 1)]
+    );
+}
+
+#[test]
+fn count_with_for_each_record() {
+    let data = include_str!("../tests/ants.dat");
+    let mut turtles = 0;
+    let mut sections = vec![];
+    for_each_record(data.as_bytes(), |section, record| {
+        if section == Section::Turtles {
+            assert_ne!(&record[0], "who");
+            turtles += 1;
+        }
+        if !sections.contains(&section) {
+            sections.push(section);
+        }
+    })
+    .expect("parse failed");
+    assert_eq!(turtles, 6);
+    assert_eq!(
+        sections[..3],
+        [Section::Header, Section::RandomState, Section::Globals]
+    );
+
+    // Breed headers aren't rows
+    let data = r#""TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","wool"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","true"
+"#;
+    let mut whos = vec![];
+    for_each_record(data.as_bytes(), |_, record| {
+        whos.push(record[0].to_string())
+    })
+    .expect("parse failed");
+    assert_eq!(whos, ["0", "1"]);
+
+    // Rows of unknown sections aren't tagged with the previous section
+    let unknown = format!("{}\n\"FOOBAR\"\n\"1\",\"2\"\n", data);
+    let mut whos = vec![];
+    for_each_record(unknown.as_bytes(), |_, record| {
+        whos.push(record[0].to_string())
+    })
+    .expect("parse failed");
+    assert_eq!(whos, ["0", "1"]);
+}
+
+#[test]
+fn for_each_record_with_options() {
+    let count = |data: &str, options: ParseOptions| {
+        let mut turtles = 0;
+        for_each_record_with(data.as_bytes(), options, |section, _| {
+            turtles += (section == Section::Turtles) as usize;
+        })
+        .expect("parse failed");
+        turtles
+    };
+    let options = ParseOptions {
+        delimiter: b';',
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        count(include_str!("../tests/ants-semicolon.dat"), options),
+        6
+    );
+    let options = ParseOptions {
+        comment: Some(b';'),
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        count(include_str!("../tests/ants-annotated.dat"), options),
+        6
+    );
+    let data = include_str!("../tests/ants-bom.dat");
+    assert_eq!(count(data, ParseOptions::default()), 6);
+
+    let options = ParseOptions {
+        sections: SectionMask::none().with(Section::Patches),
+        ..ParseOptions::default()
+    };
+    let mut sections = vec![];
+    let data = include_str!("../tests/ants.dat");
+    for_each_record_with(data.as_bytes(), options, |section, _| {
+        sections.push(section)
+    })
+    .expect("parse failed");
+    assert_eq!(sections.len(), 25);
+    assert!(sections.iter().all(|section| *section == Section::Patches));
+}
+
+#[test]
+fn parse_repeated_sections() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks"
+"-1","1","-1","1","3"
+
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up"""
+"2","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up"""
+
+"PATCHES"
+"pxcor","pycor","pcolor","plabel","plabel-color"
+"-1","1","5","""""","9.9"
+
+"TURTLES"
+
+"PATCHES"
+"pycor","pxcor","pcolor","plabel","plabel-color"
+"1","0","15","""""","9.9"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let coords: Vec<(i64, i64)> = world.patches.iter().map(|p| (p.pxcor, p.pycor)).collect();
+    assert_eq!(coords, [(-1, 1), (0, 1)]);
+    let whos: Vec<usize> = world.turtles.iter().map(|turtle| turtle.who).collect();
+    assert_eq!(whos, [0, 1, 2]);
+    assert_eq!(world.globals.ticks, 3.0);
+    assert_eq!(
+        world.sections_seen(),
+        [Section::Turtles, Section::Globals, Section::Patches]
+    );
+    assert_eq!(world.record_counts()[&Section::Turtles], 3);
+    #[cfg(feature = "custom-fields")]
+    assert!(!world.turtles[1].has("energy"));
+}
+
+#[test]
+fn parse_empty_input() {
+    for data in &["", "\n\n", "\"export-world data (NetLogo 6.1.0)\"\n"] {
+        match parse_str(data) {
+            Err(ParseError::Empty) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(parse_lines(data.lines()), Err(ParseError::Empty)));
+        assert!(matches!(
+            parse_many(data.as_bytes()),
+            Err(ParseError::Empty)
+        ));
+    }
+    assert_eq!(ParseError::Empty.to_string(), "No sections found");
+
+    let world = parse_str("\"GLOBALS\"\n").expect("parse failed");
+    assert_eq!(world.sections_seen(), [Section::Globals]);
+}
+
+#[test]
+#[cfg(not(feature = "custom-fields"))]
+fn ignore_own_variables() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks","flock","my-name"
+"-1","1","-1","1","3","[1 ""two"" [3]]","""Bob, """"the"""" best"""
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy","home"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","NaN","{patch 0 1}"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.ticks, 3.0);
+    assert_eq!(world.turtles[0].breed, "wolves");
+    let fields = world.turtle_fields();
+    assert_eq!(fields[fields.len() - 2..], ["energy", "home"]);
+}
+
+#[test]
+fn parse_pen_colors_and_modes() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let colors: Vec<f64> = world.plots[0]
+        .pens
+        .iter()
+        .map(|pen| pen.pen_color)
+        .collect();
+    assert_eq!(colors, [85.0, 95.0, 105.0]);
+    assert!(world.plots[0]
+        .pens
+        .iter()
+        .all(|pen| pen.pen_mode == PenMode::Line));
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"PLOTS"
+"Histogram"
+"""Histogram"""
+"x min","x max","y min","y max","autoplot?","current pen","legend open?","number of pens"
+"0","10","0","10","true","""bars""","false","2"
+
+"pen name","pen down?","mode","interval","color","x"
+"""bars""","true","1","1","-16777216","0"
+"""dots""","true","2","1","15","0"
+
+"""bars""",,,,"""dots""",,,
+"x","y","color","pen down?","x","y","color","pen down?"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let pens = &world.plots[0].pens;
+    assert_eq!(
+        (pens[0].pen_mode, pens[0].pen_color),
+        (PenMode::Bar, -16777216.0)
+    );
+    assert_eq!(
+        (pens[1].pen_mode, pens[1].pen_color),
+        (PenMode::Point, 15.0)
+    );
+
+    let mut out = vec![];
+    write(&world, &mut out).expect("write failed");
+    let reparsed = parse_bytes(&out).expect("reparse failed");
+    assert_eq!(reparsed.plots, world.plots);
+
+    let invalid = data.replace(r#""true","2","1""#, r#""true","7","1""#);
+    assert!(parse_str(&invalid).is_err());
+}
+
+#[test]
+fn compute_turtle_density() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    // Six ants on a 5x5 world
+    assert_eq!(world.turtle_density(), Some(6.0 / 25.0));
+
+    let options = ParseOptions {
+        sections: SectionMask::all().without(Section::Patches),
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert!(world.patches.is_empty());
+    assert_eq!(world.turtle_density(), Some(6.0 / 25.0));
+
+    let data = data.replace(
+        r#""-2","2","-2","2","0","nobody""#,
+        r#""-2","2","2","-2","0","nobody""#,
+    );
+    let world = parse_str(&data).expect("parse failed");
+    assert_eq!(world.globals.height(), -3);
+    assert_eq!(world.turtle_density(), None);
+}
+
+#[test]
+fn normalize_keys_with_breed_headers() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"WHO","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+"WHO","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","wool"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","true"
+"#;
+    let options = ParseOptions {
+        normalize_keys: true,
+        ..ParseOptions::default()
+    };
+    let world = parse_with(data.as_bytes(), options).expect("parse failed");
+    assert_eq!(world.turtles.len(), 2);
+    assert_eq!(world.turtles[1].who, 1);
+    assert_eq!(world.turtles[1].breed, "sheep");
+    assert_eq!(world.record_counts()[&Section::Turtles], 2);
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn write_breeds_with_own_variables() {
+    use netlogo_world_parser::Value;
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","energy"
+"0","15","0","0","0","""wolf""","""""","9.9","{breed wolves}","false","1","1","""up""","12"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","wool"
+"1","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","true"
+"2","9.9","0","1","1","""sheep""","""""","9.9","{breed sheep}","false","1","1","""up""","false"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let reparsed = parse_bytes(&written).expect("reparse failed");
+
+    assert_eq!(reparsed.turtles[1].get("wool"), Some(&Value::Bool(true)));
+    assert_eq!(reparsed.turtles[1].get("energy"), None);
+    assert_eq!(reparsed.turtles[0].get("wool"), None);
+    let written = String::from_utf8(written).expect("not utf-8");
+    assert_eq!(written.matches(r#""who","color""#).count(), 2);
+    assert_eq!(world, reparsed);
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn display_custom_values() {
+    use netlogo_world_parser::Value;
+
+    let world = parse_str(include_str!("../tests/ants.dat")).expect("parse failed");
+    let directed = world.globals.get("directed-links");
+    assert_eq!(directed, Some(&Value::String("NEITHER".into())));
+    assert_eq!(
+        directed.map(Value::to_string).as_deref(),
+        Some(r#""NEITHER""#)
+    );
+
+    let data = include_str!("../tests/ants.dat").replace(r#""nobody""#, r#""""say \""hi\""""""#);
+    let world = parse_str(&data).expect("parse failed");
+    let subject = world.globals.get("subject").expect("no subject");
+    assert_eq!(subject, &Value::String(r#"say "hi""#.into()));
+    assert_eq!(subject.to_string(), r#""say \"hi\"""#);
+}
+
+#[test]
+fn parse_missing_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/missing.dat");
+    match parse_file(path) {
+        Err(ParseError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_turtle_variables() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let turtle = &world.turtles[0];
+    assert_eq!((turtle.who, turtle.color), (0, Color::Index(15.0)));
+    assert_eq!((turtle.xcor, turtle.ycor), (0, 0));
+    assert_eq!(turtle.heading, 214.0);
+    assert_eq!(turtle.shape, "bug");
+    assert_eq!(turtle.label, None);
+    assert_eq!(turtle.label_color, 9.9);
+    assert_eq!(turtle.size, 2.0);
+    assert_eq!(turtle.pen_size, 1.0);
+    assert_eq!(turtle.pen_mode, "up");
+    assert!(!turtle.hidden);
+    assert_eq!(world.turtles[2].heading, 270.0);
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn parse_nobody_global() {
+    use netlogo_world_parser::Value;
+
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let subject = world.globals.get("subject").expect("no subject");
+    assert_eq!(subject, &Value::Nobody);
+    assert!(subject.is_nobody());
+}
+
+/// Compare worlds field by field.
+fn assert_same_world(a: &NetLogoWorld, b: &NetLogoWorld) {
+    assert_eq!(a.header, b.header);
+    assert_eq!(a.random_state, b.random_state);
+    assert_eq!(a.output, b.output);
+    assert_eq!(a.globals.ticks, b.globals.ticks);
+    assert_eq!(
+        (a.globals.min_pxcor, a.globals.max_pxcor),
+        (b.globals.min_pxcor, b.globals.max_pxcor)
+    );
+    assert_eq!(a.turtles.len(), b.turtles.len());
+    for (a, b) in a.turtles.iter().zip(&b.turtles) {
+        assert_eq!((a.heading, &a.shape, a.size), (b.heading, &b.shape, b.size));
+        assert_eq!(a.breed, b.breed);
+    }
+    assert_eq!(a.patches.len(), b.patches.len());
+    for (a, b) in a.patches.iter().zip(&b.patches) {
+        assert_eq!((a.pxcor, a.pycor, a.pcolor), (b.pxcor, b.pycor, b.pcolor));
+        assert_eq!(a.plabel, b.plabel);
+    }
+    assert_eq!(a.links.len(), b.links.len());
+    assert_eq!(a.current_plot, b.current_plot);
+    assert_eq!(format!("{:?}", a.plots), format!("{:?}", b.plots));
+    assert_eq!(format!("{:?}", a.extensions), format!("{:?}", b.extensions));
+}
+
+#[test]
+fn write_round_trip() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+
+    let mut written = vec![];
+    write(&world, &mut written).expect("write failed");
+    let written = String::from_utf8(written).expect("invalid utf-8");
+    let reparsed = parse_str(&written).expect("reparse failed");
+    assert_same_world(&world, &reparsed);
+
+    #[cfg(feature = "custom-fields")]
+    {
+        assert_eq!(
+            reparsed.globals.get("population"),
+            world.globals.get("population")
+        );
+        assert_eq!(
+            reparsed.globals.get("subject"),
+            world.globals.get("subject")
+        );
+        assert_eq!(
+            reparsed.globals.get("directed-links"),
+            world.globals.get("directed-links")
+        );
+    }
+}
+
+#[test]
+fn turtle_rgb() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtles[0].rgb(), (215, 50, 41));
+}
+
+#[test]
+fn stream_events() {
+    let data = include_str!("../tests/ants.dat");
+    let mut reader = WorldReader::new(data.as_bytes());
+    let mut turtles = 0;
+    let mut patches = 0;
+    let mut plots = 0;
+    for event in reader.events() {
+        match event.expect("parse failed") {
+            Event::Turtle(_) => turtles += 1,
+            Event::Patch(_) => patches += 1,
+            Event::Plot(_) => plots += 1,
+            _ => {}
+        }
+    }
+    assert_eq!((turtles, patches, plots), (6, 25, 1));
+    assert!(reader.next_event().expect("read failed").is_none());
+}
+
+#[test]
+fn parse_random_state() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let state = world.random_state.expect("no random state");
+    assert_eq!(state.words.len(), 624);
+    assert_eq!(state.position, 273);
+    assert_eq!(state.constants, [0, 0, -1727483681]);
+    assert!(!state.have_next_next_gaussian);
+    assert_eq!(state.words[623], -494519369);
+}
+
+#[test]
+fn parse_random_state_encodings() {
+    let data = include_str!("../tests/ants.dat");
+    let line = data.lines().nth(5).expect("no random state");
+    let expected = parse_str(data).expect("parse failed").random_state;
+    assert!(expected.is_some());
+
+    let values = line.trim_matches('"');
+    let bracketed = data.replace(line, &format!("\"[{}]\"", values));
+    let world = parse_str(&bracketed).expect("parse failed");
+    assert_eq!(world.random_state, expected);
+
+    let fields: Vec<String> = values
+        .split(' ')
+        .map(|value| format!("\"{}\"", value))
+        .collect();
+    let csv = data.replace(line, &fields.join(","));
+    let world = parse_str(&csv).expect("parse failed");
+    assert_eq!(world.random_state, expected);
+}
+
+#[test]
+fn malformed_plots() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"PLOTS"
+"Population"
+"""turtles""","true","0","1","105","0"
+"#;
+    match parse_str(data) {
+        Err(ParseError::InvalidSection { section, line, .. }) => {
+            assert_eq!((section.as_str(), line), ("PLOTS", 4))
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn truncated_random_state() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"RANDOM STATE"
+"0 0 -1727483681 273 0.0 false 1048414003 1573069836"
+"#;
+    match parse_str(data) {
+        Err(ParseError::InvalidRandomState { line, .. }) => assert_eq!(line, 3),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn list_valued_globals() {
+    use netlogo_world_parser::Value;
+
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"GLOBALS"
+"min-pxcor","max-pxcor","min-pycor","max-pycor","ticks","history","names","empty"
+"-1","1","-1","1","3","[1 2.5 [3 -4]]","[""a"" ""b""]","[]"
+"#;
+    let world = parse_str(data).expect("parse failed");
+    assert_eq!(world.globals.ticks, 3.0);
+    assert_eq!(
+        world.globals.get("history"),
+        Some(&Value::List(vec![
+            Value::U64(1),
+            Value::Float(2.5),
+            Value::List(vec![Value::U64(3), Value::I64(-4)]),
+        ]))
+    );
+    assert_eq!(
+        world.globals.get("names"),
+        Some(&Value::List(vec![
+            Value::String("a".into()),
+            Value::String("b".into())
+        ]))
+    );
+    assert_eq!(world.globals.get("empty"), Some(&Value::List(vec![])));
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn normalize_custom_keys() {
+    use netlogo_world_parser::Value;
+
+    let data = include_str!("../tests/ants.dat");
+    let shouting = data
+        .replace(r#""nest-scent""#, r#""NEST-SCENT""#)
+        .replace(r#""population""#, r#""Population""#);
+    let options = ParseOptions {
+        normalize_keys: true,
+        ..ParseOptions::default()
+    };
+    for data in &[data, &shouting] {
+        let world = parse_with(data.as_bytes(), options.clone()).expect("parse failed");
+        assert_eq!(world.patches[12].get("nest-scent"), Some(&Value::U64(200)));
+        assert_eq!(world.globals.get("population"), Some(&Value::U64(6)));
+    }
+
+    let world = parse_with(shouting.as_bytes(), options).expect("parse failed");
+    assert!(world
+        .patch_fields()
+        .iter()
+        .any(|field| field == "NEST-SCENT"));
+    let world = parse_str(&shouting).expect("parse failed");
+    assert_eq!(world.patches[12].get("nest-scent"), None);
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn custom_agent_fields() {
+    use netlogo_world_parser::Value;
+
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+
+    let patch = &world.patches[12];
+    assert_eq!((patch.pxcor, patch.pycor), (0, 0));
+    assert_eq!(patch.get("nest-scent"), Some(&Value::U64(200)));
+    assert_eq!(
+        patch.get("nest?"),
+        Some(&netlogo_world_parser::Value::Bool(true))
+    );
+    assert_eq!(patch.get("chemical"), Some(&Value::U64(0)));
+    assert_eq!(patch.get("pxcor"), None);
+    assert_eq!(patch.get("missing"), None);
+
+    let turtle = &world.turtles[0];
+    assert_eq!(turtle.get("breed"), None);
+}
+
+#[test]
+fn quoted_commas_stay_in_field() {
+    let data = r#""export-world data (NetLogo 6.1.0)"
+"TURTLES"
+"who","color","heading","xcor","ycor","shape","label","label-color","breed","hidden?","size","pen-size","pen-mode","home"
+"0","15","0","0","0","""default""","""north, east""","9.9","{turtles}","false","1","1","""up""","""a, b, c"""
+"#;
+    let world = parse_str(data).expect("parse failed");
+    let turtle = &world.turtles[0];
+    assert_eq!(turtle.label.as_deref(), Some("north, east"));
+    assert_eq!(turtle.pen_mode, "up");
+
+    #[cfg(feature = "custom-fields")]
+    assert_eq!(
+        turtle.get("home"),
+        Some(&netlogo_world_parser::Value::String("a, b, c".into()))
+    );
+}
+
+#[test]
+fn parse_header() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+    let header = world.header.expect("no header");
+    assert_eq!(header.version, "6.1.0");
+    assert_eq!(header.model.as_deref(), Some("Ants.nlogo"));
+    assert_eq!(header.exported_at, "08/07/2019 15:20:03:864 +0200");
+}
+
+#[test]
+fn find_agents() {
+    let data = include_str!("../tests/ants.dat");
+    let mut world = parse_str(data).expect("parse failed");
+    assert_eq!(world.turtle(3).map(|turtle| turtle.heading), Some(33.0));
+    assert!(world.turtle(42).is_none());
+
+    world.turtles.reverse();
+    assert_eq!(world.turtle(1).map(|turtle| turtle.heading), Some(266.0));
+
+    let patch = world.patch_at(1, -2).expect("no patch");
+    assert_eq!((patch.pxcor, patch.pycor), (1, -2));
+    assert!(world.patch_at(3, 0).is_none());
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn parse_zip() {
+    use std::io::{Cursor, Write};
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    let data = include_str!("../tests/ants.dat");
+    let mut writer = ZipWriter::new(Cursor::new(vec![]));
+    writer
+        .start_file("readme.txt", SimpleFileOptions::default())
+        .expect("zip failed");
+    writer.write_all(b"not a world").expect("zip failed");
+    writer
+        .start_file("worlds/ants.dat", SimpleFileOptions::default())
+        .expect("zip failed");
+    writer.write_all(data.as_bytes()).expect("zip failed");
+    let archive = writer.finish().expect("zip failed").into_inner();
+
+    let world = netlogo_world_parser::parse_zip(Cursor::new(&archive), "worlds/ants.dat")
+        .expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+
+    match netlogo_world_parser::parse_zip(Cursor::new(&archive), "missing.dat") {
+        Err(ParseError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn parse_gzip() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let data = include_str!("../tests/ants.dat");
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data.as_bytes()).expect("compress failed");
+    let compressed = encoder.finish().expect("compress failed");
+
+    let world = netlogo_world_parser::parse_gz(compressed.as_slice()).expect("parse failed");
+    assert_eq!(world.turtles.len(), 6);
+    assert_eq!(world.patches.len(), 25);
+}
+
+#[test]
+#[cfg(feature = "custom-fields")]
+fn custom_fields_keep_column_order() {
+    let data = include_str!("../tests/ants.dat");
+    let world = parse_str(data).expect("parse failed");
+
+    let globals: Vec<&str> = world.globals.custom_fields().map(|(key, _)| key).collect();
+    assert_eq!(
+        globals,
+        vec![
+            "perspective",
+            "subject",
+            "nextIndex",
+            "directed-links",
+            "diffusion-rate",
+            "evaporation-rate",
+            "population"
+        ]
+    );
+
+    let patch: Vec<&str> = world.patches[0]
+        .custom_fields()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(
+        patch,
+        vec![
+            "chemical",
+            "food",
+            "nest?",
+            "nest-scent",
+            "food-source-number"
+        ]
+    );
+}